@@ -1,68 +1,141 @@
-use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use clap::Parser;
-use tokio::{net::{TcpListener, TcpStream}, sync::RwLock};
+use futures::{stream, StreamExt};
+use ::http::Method;
+use tokio::{net::{TcpListener, TcpStream}, sync::RwLock, time::timeout};
 
 use common::{
-    parse::{settings, remote::Remote},
+    parse::{encoding::ENCODINGS_HEADER, settings, remote::Remote},
     error::{Error, Result},
-    http,
+    registry::{Catalog, Ping, Registry},
+    http::{self, read::HttpRequest},
 };
 
-async fn route_ping (stream: &mut TcpStream, info: &Remote) -> Result<()> {
-    http::write::write_http("200 OK", &serde_json::to_vec_pretty(info)?, stream).await?;
-    Ok(())
+/// Največje število hkratnih pingov pri osvežitvi registra
+const PING_CONCURRENCY: usize = 64;
+
+fn route_ping(info: &Remote) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(info)?)
 }
 
-async fn route_generator(stream: &mut TcpStream, registered: &RwLock<HashSet<Remote>>, data: &[u8]) -> Result<()> {
-    // Ločimo med primeroma, ko je body requesta prazen (in želi uporabnik pridobiti registiranje) 
-    // in primerom, ko se želi registrirati
-    if data.is_empty() {
-        let mut result = vec![];
-        {
-            let registered = registered.read().await;
-            for remote in registered.iter() {
-                result.push(remote.clone());
-            }
-        }
-        
-        http::write::write_http("200 OK", &serde_json::to_vec_pretty(&result)?, stream).await?;
-    } else {
-        let remote: Remote = serde_json::from_slice(data)?;
-        registered.write().await.insert(remote);
-
-        http::write::write_http("200 OK", &[], stream).await?;
-    }
+// vrne seznam registriranih generatorjev, skupaj s časom, ko smo jih nazadnje videli
+async fn route_generator_list(registered: &RwLock<Registry>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&registered.read().await.entries())?)
+}
+
+// pridobi zaporedja, ki jih ponuja generator, in zapise, ki jih oglašuje
+async fn fetch_catalog(remote: &Remote, timeout_length: u64) -> Result<Catalog> {
+    let response = timeout(Duration::from_secs(timeout_length), remote.get("/sequence/", None)).await??;
+    let encodings = response.header(ENCODINGS_HEADER).map(str::to_owned);
+    let sequences = serde_json::from_slice(&remote.expect_success(response)?)?;
+    Ok(Catalog { sequences, encodings })
+}
 
-    Ok(())
+// registrira generator iz telesa requesta. Katalog generatorja poskusimo pridobiti takoj, 
+// da ga drugi najdejo še pred naslednjo osvežitvijo
+async fn route_generator_register(registered: &RwLock<Registry>, data: &[u8], timeout_ping: u64) -> Result<Vec<u8>> {
+    let remote: Remote = serde_json::from_slice(data)?;
+    let catalog = fetch_catalog(&remote, timeout_ping).await.ok();
+    // zaklep registra sprostimo, preden stanje zapišemo v datoteko
+    let snapshot = registered.write().await.insert(remote, catalog)?;
+    snapshot.save().await?;
+
+    Ok(vec![])
+}
+
+// vrne zaporedja iz katalogov registriranih generatorjev, ki ustrezajo pogojem v query stringu
+async fn route_sequence_search(registered: &RwLock<Registry>, query: &[(String, String)]) -> Result<Vec<u8>> {
+    let value = |key: &str| query.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str());
+    let parameters = value("parameters").map(str::parse).transpose()?;
+    let sequences = value("sequences").map(str::parse).transpose()?;
+
+    Ok(serde_json::to_vec_pretty(&registered.read().await.search(value("name"), parameters, sequences))?)
+}
+
+// odstrani generator iz telesa requesta, če je registriran
+async fn route_generator_unregister(registered: &RwLock<Registry>, data: &[u8]) -> Result<Vec<u8>> {
+    let remote: Remote = serde_json::from_slice(data)?;
+    let snapshot = registered.write().await.remove(&[remote])?;
+    if let Some(snapshot) = snapshot { snapshot.save().await?; }
+
+    Ok(vec![])
+}
+
+const GET: &[Method] = &[Method::GET];
+const GET_POST_DELETE: &[Method] = &[Method::GET, Method::POST, Method::DELETE];
+
+// vrne metode, ki jih podpira pot, če ta obstaja
+fn allowed_methods(path: &str, register_endpoint: &str) -> Option<&'static [Method]> {
+    if path == "/ping" || path == "/sequences" { Some(GET) }
+    else if path == register_endpoint { Some(GET_POST_DELETE) }
+    else { None }
+}
+
+// odgovori na en request glede na metodo in pot
+async fn handle_request(request: HttpRequest, stream: &mut TcpStream, registered: &RwLock<Registry>, info: &Remote, register_endpoint: &str, timeout_ping: u64) -> Result<()> {
+    let path = request.route_path();
+    let allowed = allowed_methods(path, register_endpoint).ok_or(Error::missing_path(path))?;
+    let method = match http::route::resolve_method(&request, path, allowed, stream).await? {
+        None => return Ok(()),
+        Some(method) => method
+    };
+
+    let data = match (method.as_str(), path) {
+        ("GET", "/ping")        => route_ping(info)?,
+        ("GET", "/sequences")   => route_sequence_search(registered, &request.query()).await?,
+        ("GET", _)              => route_generator_list(registered).await?,
+        ("POST", _)             => route_generator_register(registered, &request.body, timeout_ping).await?,
+        ("DELETE", _)           => route_generator_unregister(registered, &request.body).await?,
+        _ => Err(Error::missing_path(path))?
+    };
+
+    http::write::write_http_response("200 OK", "application/json", &[request.connection_header()], &data, request.method != Method::HEAD, stream).await
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let settings = settings::SettingsCentralni::parse();
+    let max_headers = settings.max_headerjev;
 
     let info = Arc::new(Remote::new("Centralni strežnik", &settings.ip.to_string(), settings.port)?);
 
     let listener = TcpListener::bind(info.get_url()).await?;
-    let registered = Arc::new(RwLock::new(HashSet::<Remote>::new()));
+    let registered = Registry::load(&settings.datoteka_registra).await?;
+    println!("Naloženih {} generatorjev iz {}", registered.remotes().count(), settings.datoteka_registra.display());
+    let registered = Arc::new(RwLock::new(registered));
 
-    // na vsake toliko časa pingamo vse registriane, če so še aktivni
+    // na vsake toliko časa (hkrati) pingamo vse registriane, če so še aktivni, in od odzivnih pridobimo katalog zaporedij.
+    // Prvič to storimo takoj ob zagonu, da iz registra odstranimo generatorje, ki so se ugasnili, medtem ko nas ni bilo.
     { 
         let registered = registered.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(settings.osvezitveni_cas));
             loop {
                 interval.tick().await;
-                let mut result = HashSet::<Remote>::new();
-                for remote in registered.read().await.iter() {
-                    if remote.ping(None, settings.timeout_ping).await.is_ok() {
-                        result.insert(remote.clone());
-                    }
+                let remotes: Vec<Remote> = registered.read().await.remotes().cloned().collect();
+                let pings: Vec<Ping> = stream::iter(remotes)
+                    .map(|remote| async move {
+                        let success = remote.ping(None, settings.timeout_ping).await.is_ok();
+                        let catalog = if success { fetch_catalog(&remote, settings.timeout_ping).await.ok() } else { None };
+                        Ping { remote, success, catalog }
+                    })
+                    .buffer_unordered(PING_CONCURRENCY)
+                    .collect().await;
+
+                // zaklep registra sprostimo, preden stanje zapišemo v datoteko
+                let recorded = registered.write().await.record_pings(pings, settings.zgreseni_pingi.max(1));
+                let saved = match recorded {
+                    Ok((evicted, snapshot)) => snapshot.save().await.map(|_| evicted),
+                    Err(err) => Err(err)
+                };
+                match saved {
+                    Ok(evicted) => {
+                        for remote in evicted { println!("Odstranjen neodziven generator {}", remote.get_url()); }
+                        println!("Osveženo!")
+                    },
+                    Err(err) => println!("Napaka pri shranjevanju registra: {}", serde_json::to_string(&err).unwrap_or_default())
                 }
-                let mut registered = registered.write().await;
-                *registered = result;
-                println!("Osveženo!")
             }
         });
     }
@@ -74,24 +147,22 @@ async fn main() -> Result<()> {
         let registered = registered.clone();
         let register_endpoint = http::helper::remove_if_trailing(&settings.register_endpoint).to_string();
 
+        // na isti povezavi beremo requeste, dokler je odjemalec ne zapre ali pa ta ni predolgo neaktivna
         tokio::spawn(async move {
-            match http::read::read_http_request(&mut stream).await {
-                Err(err) => err.send_error(&mut stream).await,
-                Ok((path, data)) => {
-
-                    let path = http::helper::remove_if_trailing(path.as_str());
-
-                    let result = {
-                        if path == "/ping" { route_ping(&mut stream, &info).await }
-                        else if path == register_endpoint { route_generator(&mut stream, &registered, &data).await }
-                        else { Err(Error::missing_path(&path)) }
-                    };
-
-                    match result {
-                        Ok(()) => (),
-                        Err(error) => error.send_error(&mut stream).await
-                    }
+            let mut buffer = http::read::ReadBuffer::new(max_headers);
+            loop {
+                let request = match timeout(http::helper::KEEP_ALIVE_TIMEOUT, http::read::read_http_request(&mut stream, &mut buffer)).await {
+                    Err(_) | Ok(Ok(None)) => break,
+                    Ok(Err(err)) => { err.send_error(&mut stream).await; break },
+                    Ok(Ok(Some(request))) => request
+                };
+
+                let keep_alive = request.keep_alive();
+                match handle_request(request, &mut stream, &registered, &info, &register_endpoint, settings.timeout_ping).await {
+                    Ok(()) => (),
+                    Err(err) => err.send_error(&mut stream).await
                 }
+                if !keep_alive { break }
             }
         });
     }
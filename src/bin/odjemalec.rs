@@ -0,0 +1,152 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use clap::Parser;
+use tokio::time::timeout;
+
+use common::{
+    parse::{
+        encoding::Encoding, expression::parse_expression, parse_helper::Sendable, remote::Remote,
+        sequence_provide::{Domain, Range, Request, SequenceInfo},
+        settings::{Oblika, SettingsOdjemalec, Ukaz}
+    },
+    error::Result,
+    http::helper::remove_if_trailing
+};
+
+/// Rezultat ukaza, ki ga znamo izpisati kot tabelo, CSV ali JSON
+struct Output {
+    header: Vec<&'static str>,
+    rows:   Vec<Vec<String>>,
+    json:   serde_json::Value
+}
+
+// polje v CSV damo v narekovaje, če vsebuje ločilo, narekovaj ali novo vrstico
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else { field.to_owned() }
+}
+
+fn print_output(output: &Output, oblika: Oblika) -> Result<()> {
+    match oblika {
+        Oblika::Json => println!("{}", serde_json::to_string_pretty(&output.json)?),
+        Oblika::Csv => {
+            println!("{}", output.header.join(","));
+            for row in &output.rows {
+                println!("{}", row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+            }
+        },
+        Oblika::Tabela => {
+            let widths: Vec<usize> = output.header.iter().enumerate()
+                .map(|(i, name)| output.rows.iter().map(|row| row[i].chars().count()).chain([name.chars().count()]).max().unwrap_or(0))
+                .collect();
+            let line = |row: Vec<&str>| row.iter().zip(&widths)
+                .map(|(field, width)| format!("{field:<width$}"))
+                .collect::<Vec<_>>().join("  ").trim_end().to_owned();
+
+            let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            println!("{}", line(output.header.clone()));
+            println!("{}", line(separator.iter().map(String::as_str).collect()));
+            for row in &output.rows {
+                println!("{}", line(row.iter().map(String::as_str).collect()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn generator(ip: IpAddr, port: u16) -> Result<Remote> {
+    Remote::new("Generator", &ip.to_string(), port)
+}
+
+// seznam generatorjev, registriranih na centralnem strežniku
+async fn list_generators(central_server: &Remote, register_endpoint: &str) -> Result<Output> {
+    let data = central_server.expect_success(central_server.get(register_endpoint, None).await?)?;
+    let generators: Vec<Remote> = serde_json::from_slice(&data)?;
+
+    Ok(Output {
+        header: vec!["name", "ip", "port"],
+        rows: generators.iter().map(|remote| vec![remote.name.clone(), remote.ip.to_string(), remote.port.to_string()]).collect(),
+        json: serde_json::to_value(&generators)?
+    })
+}
+
+// seznam zaporedij, ki jih ponuja generator
+async fn list_sequences(generator: &Remote) -> Result<Output> {
+    let data = generator.expect_success(generator.get("/sequence/", None).await?)?;
+    let sequences: Vec<SequenceInfo> = serde_json::from_slice(&data)?;
+
+    Ok(Output {
+        header: vec!["name", "parameters", "sequences", "description"],
+        rows: sequences.iter().map(|info| vec![
+            info.name.clone(), info.parameters.to_string(), info.sequences.to_string(), info.description.clone()
+        ]).collect(),
+        json: serde_json::to_value(&sequences)?
+    })
+}
+
+// členi zaporedja, podanega z izrazom, ki jih pridobimo z navadnim `POST /sequence/<ime>`.
+// Točne člene generator pošlje kot JSON nize, ki jih izpišemo nespremenjene.
+async fn request_terms(generator: &Remote, expression: &str, range: Range, domain: Domain) -> Result<Output> {
+    let sequence = parse_expression(expression)?;
+    let request = Request {
+        range,
+        parameters: sequence.parameters,
+        sequences: sequence.sequences,
+        visited: vec![],
+        deadline_ms: None,
+        deadline: None,
+        domain
+    }.validate()?;
+
+    let endpoint = format!("/sequence/{}", sequence.name);
+    let encoding = if domain.is_float() { Encoding::Binary } else { Encoding::CompactJson };
+    let accept = ("Accept", encoding.accept());
+    let response = generator.post_with_headers(&endpoint, &[accept], &request.as_sendable()?, None).await?;
+    let encoding = Encoding::from_content_type(response.header("content-type"));
+    let data = generator.expect_success(response)?;
+
+    let (values, json) = if domain.is_float() {
+        let terms = encoding.decode(&data)?;
+        (terms.iter().map(f64::to_string).collect::<Vec<_>>(), serde_json::to_value(&terms)?)
+    } else {
+        let terms: Vec<String> = serde_json::from_slice(&data)?;
+        (terms.clone(), serde_json::to_value(&terms)?)
+    };
+
+    let indices = (range.from..range.to).step_by(range.step.try_into()?);
+    Ok(Output {
+        header: vec!["n", "value"],
+        rows: indices.zip(values).map(|(n, value)| vec![n.to_string(), value]).collect(),
+        json
+    })
+}
+
+async fn run(settings: &SettingsOdjemalec) -> Result<Output> {
+    match &settings.ukaz {
+        Ukaz::Generatorji { centralni_ip, centralni_port, register_endpoint } => {
+            let central_server = Remote::new("Centralni strežnik", &centralni_ip.to_string(), *centralni_port)?;
+            list_generators(&central_server, remove_if_trailing(register_endpoint)).await
+        },
+        Ukaz::Zaporedja { ip, port } => list_sequences(&generator(*ip, *port)?).await,
+        Ukaz::Cleni { izraz, ip, port, od, r#do, korak, domena } => {
+            request_terms(&generator(*ip, *port)?, izraz, Range { from: *od, to: *r#do, step: *korak }, *domena).await
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let settings = SettingsOdjemalec::parse();
+
+    let result = match timeout(Duration::from_secs(settings.timeout), run(&settings)).await {
+        Ok(result) => result,
+        Err(elapsed) => Err(elapsed.into())
+    };
+
+    // napake izpišemo v obliki, v kateri jih pošiljata strežnika
+    if let Err(err) = result.and_then(|output| print_output(&output, settings.oblika)) {
+        eprintln!("{}", serde_json::to_string_pretty(&err).unwrap_or_default());
+        std::process::exit(1);
+    }
+}
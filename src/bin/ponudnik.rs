@@ -1,42 +1,236 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use clap::Parser;
-use tokio::net::TcpListener;
-use tokio::{net::TcpStream, sync::RwLock};
+use ::http::Method;
+use tokio::{io::AsyncWriteExt, net::TcpListener};
+use tokio::{net::TcpStream, sync::{watch, RwLock}, task::JoinSet, time::timeout};
+use tokio::signal::unix::{signal, SignalKind};
 
 use common::{
-    parse::{remote::Remote, sequence_provide, settings}, 
-    sequnce_providers::ProviderManager, 
+    parse::{encoding::{Encoding, ENCODINGS_HEADER}, remote::Remote, sequence_provide, settings}, 
+    sequnce_providers::{exact, variants::{formula, function::FunctionSequenceProvider}, Batches, ProviderManager}, 
     error::{Error, Result}, 
-    http
+    http::{self, read::HttpRequest}
 };
 
-// če je mogoče vrne generirano zaporedje z iskano signaturo
-async fn route_sequence_generic(path: &str, data: &[u8], stream: &mut TcpStream, manager: &RwLock<ProviderManager>) -> Result<()> {
-    let request = sequence_provide::parse_request(&data)?;
-    
-    let result = manager.read().await
-        .find(&request.get_info(path))?
-        .provide(request, manager).await?;
+/// Največje število členov, v katerih iščemo rekurzijo, saj Berlekamp-Massey potrebuje O(n^2) operacij
+const MAX_ANALYZE_TERMS: usize = 4096;
 
-    http::write::write_http("200 OK", &serde_json::to_vec_pretty(&result)?, stream).await
+// če je mogoče pošlje generirano zaporedje z iskano signaturo v zapisu, ki ga želi odjemalec. 
+// Dolga zaporedja pošiljamo po kosih, medtem ko jih računamo.
+async fn route_sequence_generic(name: &str, request: &HttpRequest, manager: &RwLock<ProviderManager>, stream: &mut TcpStream) -> Result<()> {
+    let sequence_request = sequence_provide::parse_request(&request.body)?;
+    send_sequence(name, sequence_request, request, manager, stream).await
+}
+
+// kot route_sequence_generic, le da je zaporedje podano z izrazom, npr. `sum(arithmetic(0,2), const(1))`
+async fn route_query(request: &HttpRequest, manager: &RwLock<ProviderManager>, stream: &mut TcpStream) -> Result<()> {
+    let (name, sequence_request) = sequence_provide::parse_query(&request.body)?;
+    send_sequence(&name, sequence_request, request, manager, stream).await
+}
+
+async fn send_sequence(name: &str, sequence_request: sequence_provide::Request, request: &HttpRequest, manager: &RwLock<ProviderManager>, stream: &mut TcpStream) -> Result<()> {
+    if !sequence_request.domain.is_float() {
+        return send_exact_sequence(name, sequence_request, request, manager, stream).await;
+    }
+    let encoding = Encoding::negotiate(request.header("accept"))?;
+
+    if sequence_request.range.len() < manager.read().await.stream_threshold {
+        let result = ProviderManager::provide(name, sequence_request, manager).await?;
+        let data = encoding.encode(&result)?;
+        return http::write::write_http_response("200 OK", encoding.content_type(), &[request.connection_header()], &data, true, stream).await;
+    }
+
+    let terms = ProviderManager::provide_stream(name, sequence_request, manager).await?;
+    stream_terms(terms, encoding, request, stream).await
+}
+
+// točne člene pošlje kot JSON seznam nizov, saj jih drugi zapisi ne morejo predstaviti
+async fn send_exact_sequence(name: &str, sequence_request: sequence_provide::Request, request: &HttpRequest, manager: &RwLock<ProviderManager>, stream: &mut TcpStream) -> Result<()> {
+    let encoding = Encoding::negotiate_from(request.header("accept"), &[Encoding::Json, Encoding::CompactJson])?;
+    let terms = exact::to_strings(&ProviderManager::provide_exact(name, sequence_request, manager).await?);
+    let data = match encoding {
+        Encoding::CompactJson => serde_json::to_vec(&terms)?,
+        _ => serde_json::to_vec_pretty(&terms)?
+    };
+    http::write::write_http_response("200 OK", encoding.content_type(), &[request.connection_header()], &data, true, stream).await
+}
+
+// po kosih pošlje člene v podanem zapisu. Ko je status že poslan, napake pri računanju ne moremo več sporočiti,
+// zato povezavo zapremo brez zadnjega kosa, da odjemalec ve, da telo ni celo. Če pošiljanje ne uspe, s `batches`
+// zapremo tudi kanal, tako da se računanje ustavi.
+async fn stream_terms(mut batches: Batches, encoding: Encoding, request: &HttpRequest, stream: &mut TcpStream) -> Result<()> {
+    http::write::write_chunked_response_start("200 OK", encoding.content_type(), &[request.connection_header()], stream).await?;
+
+    let mut buffer = encoding.start().to_vec();
+    let mut i = 0;
+    while let Some(batch) = batches.recv().await {
+        match batch {
+            Ok(batch) => for term in batch {
+                encoding.write_term(i, term, &mut buffer)?;
+                i += 1;
+            },
+            Err(err) => {
+                println!("Napaka med pretakanjem zaporedja: {}", serde_json::to_string(&err)?);
+                stream.shutdown().await?;
+                return Ok(());
+            }
+        }
+        http::write::write_chunk(&buffer, stream).await?;
+        buffer.clear();
+    }
+
+    buffer.extend_from_slice(encoding.end());
+    http::write::write_chunk(&buffer, stream).await?;
+    http::write::write_chunked_end(stream).await
 }
 
 // vrne seznam zaporedij
-async fn route_sequence(stream: &mut TcpStream, manager: &RwLock<ProviderManager>) -> Result<()> {
-    let data: Vec<u8> = serde_json::to_vec_pretty(&manager.read().await.get_info())?;
-    http::write::write_http("200 OK", &data, stream).await
+async fn route_sequence(manager: &RwLock<ProviderManager>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&manager.read().await.get_info())?)
+}
+
+// vrne statistiko cache-a zaporedij
+async fn route_cache(manager: &RwLock<ProviderManager>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&manager.read().await.cache_stats())?)
+}
+
+// vrne zdravje oddaljenih generatorjev
+async fn route_providers(manager: &RwLock<ProviderManager>) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&manager.read().await.health_reports())?)
+}
+
+// administrativne poti so dostopne z žetonom, če je ta nastavljen, sicer pa le z loopback naslova
+fn check_admin(request: &HttpRequest, peer: SocketAddr, admin_token: Option<&str>) -> Result<()> {
+    let allowed = match admin_token {
+        Some(token) => request.header("authorization") == Some(&format!("Bearer {token}")),
+        None => peer.ip().is_loopback()
+    };
+    if allowed { Ok(()) } else { Err(Error::forbidden(&request.path)) }
+}
+
+// med delovanjem registrira novo zaporedje, podano s formulo, in vrne njegove informacije
+async fn route_admin_sequence(request: &HttpRequest, manager: &RwLock<ProviderManager>) -> Result<Vec<u8>> {
+    let definition: sequence_provide::FormulaDefinition = serde_json::from_slice(&request.body)?;
+    let sequence = formula::Sequence::new(&definition.name, &definition.formula, definition.parameters, definition.description.as_deref())?;
+
+    let info = manager.write().await.register_local(Arc::new(FunctionSequenceProvider::new(Box::new(sequence))))?;
+    println!("Registrirano zaporedje {}: {}", info.name, info.description);
+    Ok(serde_json::to_vec_pretty(&info)?)
+}
+
+// poišče najkrajšo linearno rekurzijo podanih členov in jo vrne kot zaporedje, ki ga lahko uporabimo v zahtevi
+async fn route_analyze_recurrence(request: &HttpRequest, manager: &RwLock<ProviderManager>) -> Result<Vec<u8>> {
+    let query: sequence_provide::RecurrenceQuery = serde_json::from_slice(&request.body)?;
+    if query.terms.len() > MAX_ANALYZE_TERMS { return Err(Error::too_many_terms(query.terms.len(), MAX_ANALYZE_TERMS)); }
+
+    Ok(serde_json::to_vec_pretty(&ProviderManager::analyze_recurrence(query, manager).await?)?)
+}
+
+fn route_ping(info: &Remote) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(info)?)
+}
+
+const GET: &[Method] = &[Method::GET];
+const POST: &[Method] = &[Method::POST];
+
+// vrne metode, ki jih podpira pot, če ta obstaja
+fn allowed_methods(path: &str) -> Option<&'static [Method]> {
+    match path {
+        "/sequence" | "/ping" | "/cache" | "/providers" => Some(GET),
+        "/query" | "/admin/sequence" | "/analyze/recurrence" => Some(POST),
+        path if path.starts_with("/sequence/") => Some(POST),
+        _ => None
+    }
 }
 
-async fn route_ping (stream: &mut TcpStream, info: &Remote) -> Result<()> {
-    let data = serde_json::to_vec_pretty(info)?;
-    http::write::write_http("200 OK", &data, stream).await
+// odgovori na en request glede na metodo in pot
+async fn handle_request(request: HttpRequest, peer: SocketAddr, stream: &mut TcpStream, manager: &RwLock<ProviderManager>, info: &Remote, admin_token: Option<&str>) -> Result<()> {
+    let path = request.route_path();
+    let allowed = allowed_methods(path).ok_or(Error::missing_path(path))?;
+    let method = match http::route::resolve_method(&request, path, allowed, stream).await? {
+        None => return Ok(()),
+        Some(method) => method
+    };
+
+    let data = match (method.as_str(), path) {
+        ("GET", "/sequence")    => route_sequence(manager).await?,
+        ("GET", "/ping")        => route_ping(info)?,
+        ("GET", "/cache")       => route_cache(manager).await?,
+        ("GET", "/providers")   => route_providers(manager).await?,
+        ("POST", "/query")      => return route_query(&request, manager, stream).await,
+        ("POST", "/analyze/recurrence") => route_analyze_recurrence(&request, manager).await?,
+        ("POST", "/admin/sequence") => {
+            check_admin(&request, peer, admin_token)?;
+            route_admin_sequence(&request, manager).await?
+        },
+        ("POST", path)          => match path.strip_prefix("/sequence/") {
+            Some(name) => return route_sequence_generic(name, &request, manager, stream).await,
+            None => Err(Error::missing_path(path))?
+        },
+        (_, path) => Err(Error::missing_path(path))?
+    };
+
+    // na seznamu zaporedij oglašujemo še zapise, v katerih jih lahko pošljemo
+    let mut headers = vec![request.connection_header()];
+    if path == "/sequence" { headers.push((ENCODINGS_HEADER, Encoding::advertise())); }
+
+    http::write::write_http_response("200 OK", "application/json", &headers, &data, request.method != Method::HEAD, stream).await
 }
 
 // registrira sebe na endpoint /generator/, centralnega strežnika 
 async fn register(register_endpoint: &str, central_server: &Remote, info: &Remote) -> Result<()> {
-    let (reason, status, data) = central_server.post(register_endpoint, &serde_json::to_vec_pretty(&info)?, None).await?;
-    if (reason, status) == ("OK".to_owned(), 200) { Ok(()) } else { Err(Error::remote_invalid_response(&central_server.get_url(), &data)) }
+    let response = central_server.post(register_endpoint, &serde_json::to_vec_pretty(&info)?, None).await?;
+    central_server.expect_success(response).map(|_| ())
+}
+
+// odjavi sebe pri centralnem strežniku, da nas ta ne ponuja več do naslednjega pinga
+async fn unregister(register_endpoint: &str, central_server: &Remote, info: &Remote) -> Result<()> {
+    let response = central_server.delete(register_endpoint, &serde_json::to_vec_pretty(&info)?, None).await?;
+    central_server.expect_success(response).map(|_| ())
+}
+
+// počaka na SIGINT ali SIGTERM
+async fn shutdown_signal() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => ()
+    }
+    Ok(())
+}
+
+/// Čas do prvega ponovnega poskusa registracije, ki se ob vsakem neuspehu podvoji
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+// na vsake toliko časa se ponovno registrira (če nas je centralni strežnik medtem pozabil) in posodobi remote ponudnike.
+// Če centralni strežnik ni dosegljiv, registracijo ponavljamo vse redkeje, a vsaj enkrat na `period`.
+async fn refresh(register_endpoint: String, central_server: Arc<Remote>, info: Arc<Remote>, manager: Arc<RwLock<ProviderManager>>, period: Duration) {
+    let mut registered = None;
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let wait = match register(&register_endpoint, &central_server, &info).await {
+            Ok(()) => {
+                if registered != Some(true) { println!("Registriran pri centralnem strežniku {}", central_server.get_url()); }
+                registered = Some(true);
+                backoff = MIN_BACKOFF;
+
+                ProviderManager::update_providers(&register_endpoint, &manager).await.unwrap_or_else(|_| println!("Napaka pri posodabljanju remote providerjev."));
+                period
+            },
+            Err(err) => {
+                if registered != Some(false) {
+                    println!("Registracija pri centralnem strežniku {} ni uspela: {}", central_server.get_url(), serde_json::to_string(&err).unwrap_or_default());
+                }
+                registered = Some(false);
+                let wait = backoff;
+                backoff = (backoff * 2).min(period);
+                wait
+            }
+        };
+        tokio::time::sleep(wait).await;
+    }
 }
 
 #[tokio::main]
@@ -47,52 +241,70 @@ async fn main() -> Result<()> {
 
     let info = Arc::new(Remote::new("Anže Hočevar", &settings.ip.to_string(), settings.port)?);
     let central_server = Arc::new(Remote::new("Centralni strežnik", &settings.centralni_ip.to_string(), settings.centralni_port)?);
-    register(&register_endpoint, &central_server, &info).await?;
 
+    // najprej poslušamo, da nas centralni strežnik lahko pinga takoj po registraciji
     let listener = TcpListener::bind(info.get_url()).await?;
     let manager = Arc::new(RwLock::new(ProviderManager::new(&settings, &info, &central_server)));
+    let admin_token = Arc::new(settings.admin_zeton.clone());
+    let max_headers = settings.max_headerjev;
 
-    // na vsake tokliko časa posobimo naše remote ponudnike
-    { 
-        let manager = manager.clone();
-        let register_endpoint = register_endpoint.to_string();
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(settings.osvezitveni_cas));
-            loop {
-                interval.tick().await;
-                ProviderManager::update_providers(&register_endpoint, &manager).await.unwrap_or_else(|_| println!("Napaka pri posodabljanju remote providerjev."));
-            }
-        });
-    }
+    let refresh = tokio::spawn(refresh(
+        register_endpoint.to_string(), central_server.clone(), info.clone(), manager.clone(), Duration::from_secs(settings.osvezitveni_cas)
+    ));
+
+    // ob zaustavitvi povezave po trenutnem requestu zapremo, na tiste, ki čakajo na nov request, pa ne čakamo
+    let (shutdown_sender, shutdown) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    let signal = shutdown_signal();
+    tokio::pin!(signal);
 
     // sprejemamo requeste tukaj
     loop {
-        let (mut stream, _addr) = listener.accept().await?;
+        let (mut stream, peer) = tokio::select! {
+            result = &mut signal => { result?; break },
+            Some(_) = connections.join_next() => continue,
+            accepted = listener.accept() => accepted?
+        };
         let manager = manager.clone();
         let info = info.clone();
+        let admin_token = admin_token.clone();
+        let mut shutdown = shutdown.clone();
 
-        tokio::spawn(async move {
-            match http::read::read_http_request(&mut stream).await {
-                Err(err) => err.send_error(&mut stream).await,
-                Ok((path, data)) => {
-                    let result = match http::helper::remove_if_trailing(path.as_str()) {                        
-                        "/sequence"    => route_sequence(&mut stream, &manager).await,
-                        "/ping"        => route_ping(&mut stream, &info).await,
-                        path      => {
-                            if path.starts_with("/sequence/") {
-                                match path.get("/sequence/".len()..) {
-                                    Some(path) => route_sequence_generic(path, &data, &mut stream, &manager).await,
-                                    None => Err(Error::missing_path(path)),
-                                }
-                            } else { Err(Error::missing_path(path)) }
-                        } 
-                    };
-                    match result {
-                        Ok(()) => (),
-                        Err(err) => err.send_error(&mut stream).await
+        // na isti povezavi beremo requeste, dokler je odjemalec ne zapre ali pa ta ni predolgo neaktivna
+        connections.spawn(async move {
+            let mut buffer = http::read::ReadBuffer::new(max_headers);
+            loop {
+                let request = tokio::select! {
+                    _ = shutdown.changed() => break,
+                    request = timeout(http::helper::KEEP_ALIVE_TIMEOUT, http::read::read_http_request(&mut stream, &mut buffer)) => match request {
+                        Err(_) | Ok(Ok(None)) => break,
+                        Ok(Err(err)) => { err.send_error(&mut stream).await; break },
+                        Ok(Ok(Some(request))) => request
                     }
+                };
+
+                let keep_alive = request.keep_alive();
+                match handle_request(request, peer, &mut stream, &manager, &info, admin_token.as_deref()).await {
+                    Ok(()) => (),
+                    Err(err) => err.send_error(&mut stream).await
                 }
+                if !keep_alive || *shutdown.borrow() { break }
             }
         });
     }
+
+    // zaustavitev: najprej se odjavimo, nato počakamo (največ `rok` sekund), da se izvedejo trenutni requesti
+    println!("Zaustavljam ponudnik ...");
+    refresh.abort();
+    match unregister(register_endpoint, &central_server, &info).await {
+        Ok(()) => println!("Odjavljen pri centralnem strežniku {}", central_server.get_url()),
+        Err(err) => println!("Odjava pri centralnem strežniku ni uspela: {}", serde_json::to_string(&err).unwrap_or_default())
+    }
+
+    shutdown_sender.send_replace(true);
+    if timeout(Duration::from_secs(settings.rok), async { while connections.join_next().await.is_some() {} }).await.is_err() {
+        println!("Nekateri requesti se niso izvedli pravočasno.");
+    }
+    drop(listener);
+    Ok(())
 }
@@ -1,11 +1,12 @@
-/// Tukaj definiramo tip Result in Error, ki ga uporabljamo povsod, in 
-/// zapišemo pretvorbe med tipi Error
+//! Tukaj definiramo tip Result in Error, ki ga uporabljamo povsod, in 
+//! zapišemo pretvorbe med tipi Error
 
 use std::{io, net::AddrParseError, num::{ParseIntError, TryFromIntError}, result, str::Utf8Error};
 
+use ::http::StatusCode;
 use serde::Serialize;
 use serde_json::json;
-use tokio::{net::TcpStream, time::error::Elapsed};
+use tokio::{net::TcpStream, task::JoinError, time::error::Elapsed};
 
 use crate::{http, parse::{parse_helper::Sendable, sequence_provide}};
 
@@ -17,11 +18,19 @@ pub enum ErrorType {
     GenericParseError,
     HttpParseError,
     HttpRequestTooShort,
+    TooManyHeaders,
     MissingPath,
     MissingProvider,
     RemoteError,
     Timeout,
     ArithmeticError,
+    HopLimitExceeded,
+    BudgetExceeded,
+    MethodNotAllowed,
+    NotAcceptable,
+    AlreadyExists,
+    Forbidden,
+    UnsupportedDomain,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -101,6 +110,16 @@ impl From<Elapsed> for Error {
     }
 }
 
+impl From<JoinError> for Error {
+    fn from(value: JoinError) -> Self {
+        Error {
+            error: ErrorType::IOError,
+            message: value.to_string(),
+            extra: None
+        }
+    }
+}
+
 impl From<TryFromIntError> for Error {
     fn from(value: TryFromIntError) -> Self {
         Error {
@@ -113,6 +132,10 @@ impl From<TryFromIntError> for Error {
 
 impl Sendable for Error {}
 impl Error {
+    pub fn error_type(&self) -> &ErrorType {
+        &self.error
+    }
+
     pub fn missing_path(path: &str) -> Self {
         Error { 
             error: ErrorType::MissingPath, 
@@ -123,6 +146,33 @@ impl Error {
         }
     }
 
+    pub fn method_not_allowed(method: &str, path: &str, allow: &str) -> Self {
+        Error { 
+            error: ErrorType::MethodNotAllowed, 
+            message: "Zahtevna pot ne podpira te metode".to_owned(), 
+            extra: Some(
+                json!({
+                    "method":   method,
+                    "path":     path,
+                    "allow":    allow
+                })
+            ) 
+        }
+    }
+
+    pub fn not_acceptable(accept: &str, supported: &[&str]) -> Self {
+        Error { 
+            error: ErrorType::NotAcceptable, 
+            message: "Zaporedja ne znamo zapisati v nobeni od zahtevanih oblik".to_owned(), 
+            extra: Some(
+                json!({
+                    "accept":       accept,
+                    "supported":    supported
+                })
+            ) 
+        }
+    }
+
     pub fn http_too_long(max_length: &usize) -> Self {
         Error { 
             error: ErrorType::HttpRequestTooShort, 
@@ -133,6 +183,18 @@ impl Error {
         }
     }
 
+    pub fn too_many_headers(max_headers: usize) -> Self {
+        Error { 
+            error: ErrorType::TooManyHeaders, 
+            message: "HTTP sporočilo ima preveč headerjev".to_owned(), 
+            extra: Some(
+                json!({
+                    "max_headers":  max_headers
+                })
+            ) 
+        }
+    }
+
     pub fn missing_provider(seq: sequence_provide::SequenceInfo, close: &[sequence_provide::SequenceInfo]) -> Self {
         Error { 
             error: ErrorType::MissingProvider, 
@@ -146,6 +208,74 @@ impl Error {
         }
     }
 
+    pub fn remote_attempts_failed(seq: sequence_provide::SequenceInfo, attempted: &[String], last_error: Option<Error>) -> Self {
+        Error { 
+            error: ErrorType::RemoteError, 
+            message: "Noben od oddaljenih ponudnikov zaporedja ni uspešno odgovoril.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_query":       serde_json::to_value(seq).unwrap(),
+                    "attempted":    attempted,
+                    "last_error":   last_error
+                })
+            ) 
+        }
+    }
+
+    pub fn remotes_ejected(seq: sequence_provide::SequenceInfo, ejected: &[String]) -> Self {
+        Error { 
+            error: ErrorType::RemoteError, 
+            message: "Vsi oddaljeni ponudniki zaporedja so začasno izločeni.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_query":   serde_json::to_value(seq).unwrap(),
+                    "ejected":  ejected
+                })
+            ) 
+        }
+    }
+
+    pub fn hop_limit_exceeded(seq: sequence_provide::SequenceInfo, visited: &[String], max_hops: usize) -> Self {
+        Error { 
+            error: ErrorType::HopLimitExceeded, 
+            message: "Zahteva je bila posredovana preveliko številu generatorjev.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_query":   serde_json::to_value(seq).unwrap(),
+                    "visited":  visited,
+                    "max_hops": max_hops
+                })
+            ) 
+        }
+    }
+
+    pub fn budget_exceeded(seq: sequence_provide::SequenceInfo, budget: &str, requested: u64, limit: u64) -> Self {
+        Error { 
+            error: ErrorType::BudgetExceeded, 
+            message: "Zahteva za zaporedje presega dovoljeno omejitev.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_query":       serde_json::to_value(seq).unwrap(),
+                    "budget":       budget,
+                    "requested":    requested,
+                    "limit":        limit
+                })
+            ) 
+        }
+    }
+
+    pub fn deadline_exceeded(seq: sequence_provide::SequenceInfo) -> Self {
+        Error { 
+            error: ErrorType::Timeout, 
+            message: "Zaporedje ni bilo izračunano v dovoljenem času.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_query": serde_json::to_value(seq).unwrap()
+                })
+            ) 
+        }
+    }
+
     pub fn sequence_arithmetic_error(seq: sequence_provide::SequenceInfo, extra: &str) -> Self {
         Error { 
             error: ErrorType::ArithmeticError, 
@@ -159,6 +289,136 @@ impl Error {
         }
     }
 
+    pub fn sequence_exists(seq: sequence_provide::SequenceInfo) -> Self {
+        Error { 
+            error: ErrorType::AlreadyExists, 
+            message: "Zaporedje s to signaturo že obstaja.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_sequence": seq
+                })
+            ) 
+        }
+    }
+
+    pub fn forbidden(path: &str) -> Self {
+        Error { 
+            error: ErrorType::Forbidden, 
+            message: "Za dostop do te poti nimate pravic".to_owned(), 
+            extra: Some(
+                json!({
+                    "path": path
+                })
+            ) 
+        }
+    }
+
+    pub fn unsupported_domain(seq: sequence_provide::SequenceInfo, domain: sequence_provide::Domain) -> Self {
+        Error { 
+            error: ErrorType::UnsupportedDomain, 
+            message: "Ponudnik zaporedja ne zna računati v zahtevani domeni.".to_owned(), 
+            extra: Some(
+                json!({
+                    "_sequence":    seq,
+                    "domain":       domain
+                })
+            ) 
+        }
+    }
+
+    pub fn invalid_exact_value(value: &str, domain: sequence_provide::Domain) -> Self {
+        Error { 
+            error: ErrorType::GenericParseError, 
+            message: "Vrednost ni točno število v zahtevani domeni".to_owned(), 
+            extra: Some(
+                json!({
+                    "value":    value,
+                    "domain":   domain
+                })
+            ) 
+        }
+    }
+
+    pub fn recurrence_not_found(terms: usize, degree: usize) -> Self {
+        Error { 
+            error: ErrorType::ArithmeticError, 
+            message: "Členov je premalo, da bi zanesljivo določili linearno rekurzijo.".to_owned(), 
+            extra: Some(
+                json!({
+                    "terms":    terms,
+                    "degree":   degree,
+                    "needed":   2 * degree
+                })
+            ) 
+        }
+    }
+
+    pub fn invalid_modulus(modulus: u64, limit: u64) -> Self {
+        Error { 
+            error: ErrorType::GenericParseError, 
+            message: "Modul mora biti praštevilo, ki ni večje od 2^53, saj parametre zaporedij pošiljamo kot `f64`.".to_owned(), 
+            extra: Some(
+                json!({
+                    "modulus":  modulus,
+                    "limit":    limit
+                })
+            ) 
+        }
+    }
+
+    pub fn inexact_f64(value: &str) -> Self {
+        Error { 
+            error: ErrorType::GenericParseError, 
+            message: "Vrednosti ni mogoče točno zapisati kot `f64`, v katerem pošiljamo parametre zaporedij.".to_owned(), 
+            extra: Some(
+                json!({
+                    "value":    value
+                })
+            ) 
+        }
+    }
+
+    pub fn too_many_terms(terms: usize, limit: usize) -> Self {
+        Error { 
+            error: ErrorType::BudgetExceeded, 
+            message: "Podanih je preveč členov.".to_owned(), 
+            extra: Some(
+                json!({
+                    "budget":       "terms",
+                    "requested":    terms,
+                    "limit":        limit
+                })
+            ) 
+        }
+    }
+
+    pub fn analysis_budget_exceeded(budget: &str, requested: u64, limit: u64) -> Self {
+        Error { 
+            error: ErrorType::BudgetExceeded, 
+            message: "Iskanje rekurzije presega dovoljeno omejitev.".to_owned(), 
+            extra: Some(
+                json!({
+                    "budget":       budget,
+                    "requested":    requested,
+                    "limit":        limit
+                })
+            ) 
+        }
+    }
+
+    pub fn recurrence_too_deep(degree: usize, limit: usize) -> Self {
+        Error { 
+            error: ErrorType::MissingProvider, 
+            message: "Najdena rekurzija je daljša od največje stopnje zaporedij linear_rec_h, ki jih ponujamo.".to_owned(), 
+            extra: Some(
+                json!({
+                    "degree":   degree,
+                    "limit":    limit
+                })
+            ) 
+        }
+    }
+
     pub fn http_missing_response() -> Self {
         Error { 
             error: ErrorType::HttpParseError, 
@@ -175,6 +435,21 @@ impl Error {
         }
     }
 
+    /// Napaka v izrazu, `column` je zaporedna številka znaka (od 1 naprej), kjer je prišlo do napake
+    pub fn invalid_expression(expression: &str, column: usize, reason: &str) -> Self {
+        Error {
+            error: ErrorType::GenericParseError,
+            message: format!("Neveljaven izraz v stolpcu {column}: {reason}"),
+            extra: Some(
+                json!({
+                    "expression":   expression,
+                    "column":       column,
+                    "pointer":      format!("{}^", " ".repeat(column.saturating_sub(1)))
+                })
+            )
+        }
+    }
+
     pub fn malformed_request(extra: &str) -> Self {
         Error {
             error: ErrorType::HttpParseError,
@@ -183,21 +458,98 @@ impl Error {
         }
     }
 
-    pub fn remote_invalid_response(url: &str, error: &[u8]) -> Self {
+    pub fn remote_invalid_response(url: &str, status: u16, error: &[u8]) -> Self {
         let error_json: Option<serde_json::Value> = serde_json::from_slice(error).ok();
         Error {
             error: ErrorType::RemoteError,
             message: "Remote se je odzval narobe.".to_owned(),
             extra: Some(json!({
-                "_url":      serde_json::Value::String(url.to_owned()),
-                "info":    error_json
+                "_url":     serde_json::Value::String(url.to_owned()),
+                "status":   status,
+                "info":     error_json
             }))
         }
     }
 
+    /// HTTP status, s katerim odgovorimo na napako
+    pub fn status(&self) -> StatusCode {
+        match self.error {
+            ErrorType::IOError              => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorType::JSONParseError       => StatusCode::BAD_REQUEST,
+            ErrorType::GenericParseError    => StatusCode::BAD_REQUEST,
+            ErrorType::HttpParseError       => StatusCode::BAD_REQUEST,
+            ErrorType::HttpRequestTooShort  => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            ErrorType::TooManyHeaders       => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            ErrorType::MissingPath          => StatusCode::NOT_FOUND,
+            ErrorType::MissingProvider      => StatusCode::NOT_FOUND,
+            ErrorType::RemoteError          => StatusCode::BAD_GATEWAY,
+            ErrorType::Timeout              => StatusCode::GATEWAY_TIMEOUT,
+            ErrorType::ArithmeticError      => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorType::HopLimitExceeded     => StatusCode::LOOP_DETECTED,
+            ErrorType::BudgetExceeded       => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorType::MethodNotAllowed     => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorType::NotAcceptable        => StatusCode::NOT_ACCEPTABLE,
+            ErrorType::AlreadyExists        => StatusCode::CONFLICT,
+            ErrorType::Forbidden            => StatusCode::FORBIDDEN,
+            ErrorType::UnsupportedDomain    => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    /// Status, s katerim je odgovoril remote, če gre za napako remote-a
+    pub fn remote_status(&self) -> Option<u16> {
+        if self.error != ErrorType::RemoteError { return None; }
+        self.extra.as_ref()?.get("status")?.as_u64()?.try_into().ok()
+    }
+
+    /// Ali je smiselno zahtevo poskusiti pri drugem remote-u. Ne poskusimo znova, če je remote
+    /// zahtevo zavrnil kot napačno (4xx), razen če le ne pozna zaporedja ali je preobremenjen.
+    pub fn is_retryable(&self) -> bool {
+        match self.remote_status().and_then(|status| StatusCode::from_u16(status).ok()) {
+            Some(status) if status.is_client_error() => matches!(
+                status, StatusCode::NOT_FOUND | StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+            ),
+            _ => true
+        }
+    }
+
     // če je mogoče vrne error, drugače samo preskočimo
     pub async fn send_error(self, stream: &mut TcpStream) {
-        http::write::write_http("400 Bad Request", &self.as_sendable().unwrap_or_default(), stream).await.unwrap_or_default()
+        let status = self.status();
+        let status = format!("{} {}", status.as_u16(), status.canonical_reason().unwrap_or_default());
+
+        // pri 405 moramo povedati, katere metode so dovoljene
+        let mut headers = vec![];
+        if let (ErrorType::MethodNotAllowed, Some(extra)) = (&self.error, &self.extra) {
+            headers.push(("Allow", extra["allow"].as_str().unwrap_or_default().to_owned()));
+        }
+        http::write::write_http_response(&status, "application/json", &headers, &self.as_sendable().unwrap_or_default(), true, stream).await.unwrap_or_default()
     } 
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use ::http::StatusCode;
+    use super::Error;
+
+    #[test]
+    fn test_status() {
+        assert_eq!(Error::missing_path("/nic").status(), StatusCode::NOT_FOUND);
+        assert_eq!(Error::invalid_range().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(Error::remote_invalid_response("127.0.0.1:1", 500, &[]).status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_retryable() {
+        let remote = |status| Error::remote_invalid_response("127.0.0.1:1", status, br#"{"error": "JSONParseError"}"#);
+
+        assert_eq!(remote(400).remote_status(), Some(400));
+        assert!(!remote(400).is_retryable());
+        assert!(!remote(422).is_retryable());
+        assert!(remote(404).is_retryable());
+        assert!(remote(502).is_retryable());
+        assert!(Error::invalid_range().is_retryable());
+        assert_eq!(Error::invalid_range().remote_status(), None);
+    }
+}
@@ -1,146 +1,453 @@
 pub mod helper {
+    use std::time::Duration;
+    use ::http::Method;
+
+    /// Čas, po katerem strežnik zapre neaktivno povezavo
+    pub const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(15);
+
     pub fn remove_if_trailing(str: &str) -> &str {
-        if str.ends_with("/") {
-            &str[..str.len()-1]
-        } else { str }
+        str.strip_suffix('/').unwrap_or(str)
+    }
+
+    // dekodira `%XX` in `+` v delu query stringa
+    fn percent_decode(str: &str) -> String {
+        let bytes = str.as_bytes();
+        let mut result = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = bytes.get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (bytes[i], escaped) {
+                (b'+', _) => result.push(b' '),
+                (b'%', Some(byte)) => { result.push(byte); i += 2 },
+                (byte, _) => result.push(byte)
+            }
+            i += 1;
+        }
+        String::from_utf8_lossy(&result).into_owned()
+    }
+
+    /// Razdeli pot na del pred `?` in pare ključ-vrednost iz query stringa
+    pub fn split_query(path: &str) -> (&str, Vec<(String, String)>) {
+        match path.split_once('?') {
+            None => (path, vec![]),
+            Some((path, query)) => (path, query.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            }).collect())
+        }
+    }
+
+    /// Vrednost headerja Allow za pot, ki podpira podane metode
+    pub fn allow_header(allowed: &[Method]) -> String {
+        let mut methods: Vec<&str> = allowed.iter().map(|method| method.as_str()).collect();
+        if allowed.contains(&Method::GET) { methods.push("HEAD"); }
+        methods.push("OPTIONS");
+        methods.join(", ")
     }
 }
 
 pub mod read {
-    use std::str::from_utf8;
-    use tokio::{io::AsyncReadExt, net::TcpStream};
+    use std::{io::ErrorKind, str::from_utf8};
+    use ::http::Method;
+    use tokio::io::{AsyncRead, AsyncReadExt};
     use crate::error::{Error, Result};
     
     const BUFFER_LENGTH: usize = 16384;
 
-    /// Preberemo HTTP request, tudi če je dolg in vrnemo (path, body)
-    pub async fn read_http_request(stream: &mut TcpStream) -> Result<(String, Vec<u8>)> {
-        let mut buffer = vec![];
-        
-        loop {
-            let mut headers = [httparse::EMPTY_HEADER; 16];
-            let mut request = httparse::Request::new(&mut headers);
+    /// Največja dolžina začetne vrstice in headerjev skupaj (oziroma trailerjev)
+    const MAX_HEAD_LENGTH: usize = 4 * BUFFER_LENGTH;
+
+    /// Največja dolžina vrstice v telesu (dolžina chunk-a s parametri ali trailer)
+    const MAX_LINE_LENGTH: usize = BUFFER_LENGTH;
+
+    /// Največja dolžina telesa, da odjemalec ne more zasesti poljubno veliko pomnilnika
+    const MAX_BODY_LENGTH: usize = 64 << 20;
+
+    /// Privzeto največje število headerjev v enem sporočilu
+    pub const DEFAULT_MAX_HEADERS: usize = 64;
+
+    /// Prebran HTTP request
+    #[derive(Debug)]
+    pub struct HttpRequest {
+        pub method:     Method,
+        pub path:       String,
+        pub version:    u8,
+        pub headers:    Vec<(String, String)>,
+        pub body:       Vec<u8>
+    }
+
+    /// Prebran HTTP response
+    #[derive(Debug)]
+    pub struct HttpResponse {
+        pub reason:     String,
+        pub status:     u16,
+        pub version:    u8,
+        pub headers:    Vec<(String, String)>,
+        pub body:       Vec<u8>
+    }
+
+    fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers.iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // HTTP/1.1 povezave privzeto ostanejo odprte, HTTP/1.0 pa ne
+    fn keep_alive(version: u8, headers: &[(String, String)]) -> bool {
+        match find_header(headers, "connection") {
+            Some(connection) if connection.eq_ignore_ascii_case("close") => false,
+            Some(connection) if connection.eq_ignore_ascii_case("keep-alive") => true,
+            _ => version >= 1
+        }
+    }
+
+    impl HttpRequest {
+        /// Vrne vrednost headerja (ne glede na velikost črk v imenu)
+        pub fn header(&self, name: &str) -> Option<&str> {
+            find_header(&self.headers, name)
+        }
+
+        /// Pot brez query stringa in končne `/`, po kateri izberemo router
+        pub fn route_path(&self) -> &str {
+            super::helper::remove_if_trailing(super::helper::split_query(&self.path).0)
+        }
+
+        /// Pari ključ-vrednost iz query stringa
+        pub fn query(&self) -> Vec<(String, String)> {
+            super::helper::split_query(&self.path).1
+        }
+
+        /// Ali želi odjemalec po odgovoru povezavo obdržati odprto
+        pub fn keep_alive(&self) -> bool {
+            keep_alive(self.version, &self.headers)
+        }
+
+        /// Header Connection, ki ga pošljemo v odgovoru na ta request
+        pub fn connection_header(&self) -> (&'static str, String) {
+            ("Connection", if self.keep_alive() { "keep-alive" } else { "close" }.to_owned())
+        }
+    }
+
+    impl HttpResponse {
+        /// Vrne vrednost headerja (ne glede na velikost črk v imenu)
+        pub fn header(&self, name: &str) -> Option<&str> {
+            find_header(&self.headers, name)
+        }
+
+        /// Ali lahko povezavo po tem odgovoru ponovno uporabimo
+        pub fn keep_alive(&self) -> bool {
+            keep_alive(self.version, &self.headers)
+        }
+    }
 
-            {
-                let mut buffer_tmp = [0; BUFFER_LENGTH];
-                let read_current = stream.read(&mut buffer_tmp).await?;
+    /// Bajti, ki smo jih s povezave že prebrali, a še niso del prebranega sporočila (npr. začetek naslednjega
+    /// requesta pri pipeliningu). Hranimo ga ob povezavi, dokler je ta odprta, in ga podamo vsakemu branju z nje.
+    /// Hrani tudi največje število headerjev, ki jih na povezavi sprejmemo v enem sporočilu.
+    pub struct ReadBuffer {
+        bytes:          Vec<u8>,
+        max_headers:    usize
+    }
+
+    impl ReadBuffer {
+        pub fn new(max_headers: usize) -> Self {
+            ReadBuffer { bytes: vec![], max_headers: max_headers.max(1) }
+        }
+    }
+
+    impl Default for ReadBuffer {
+        fn default() -> Self {
+            ReadBuffer::new(DEFAULT_MAX_HEADERS)
+        }
+    }
+
+    /// Bere iz streama in hrani prebrane bajte, ki jih še nismo obdelali
+    struct Reader<'a, R> {
+        stream: &'a mut R,
+        buffer: &'a mut Vec<u8>
+    }
+
+    impl<'a, R: AsyncRead + Unpin> Reader<'a, R> {
+        fn new(stream: &'a mut R, buffer: &'a mut ReadBuffer) -> Self {
+            Reader { stream, buffer: &mut buffer.bytes }
+        }
+
+        /// Prebere nekaj bajtov v buffer in vrne, koliko jih je prebral (0 pomeni, da je povezava zaprta)
+        async fn fill(&mut self) -> std::io::Result<usize> {
+            let mut buffer_tmp = [0; BUFFER_LENGTH];
+            let read_current = self.stream.read(&mut buffer_tmp).await?;
+            self.buffer.extend_from_slice(&buffer_tmp[..read_current]);
+            Ok(read_current)
+        }
 
-                buffer.extend_from_slice(&buffer_tmp[..read_current]);
+        /// Vrne vrstico brez `\r\n` na koncu. Vrstica je lahko dolga največ `MAX_LINE_LENGTH`.
+        async fn read_line(&mut self) -> Result<Vec<u8>> {
+            loop {
+                if let Some(end) = self.buffer.iter().take(MAX_LINE_LENGTH + 2).position(|&b| b == b'\n') {
+                    let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                    line.pop();
+                    if line.last() == Some(&b'\r') { line.pop(); }
+                    return Ok(line);
+                }
+                if self.buffer.len() >= MAX_LINE_LENGTH + 2 {
+                    Err(Error::http_too_long(&MAX_LINE_LENGTH))?
+                }
+                if self.fill().await? == 0 {
+                    Err(Error::malformed_request("Povezava se je zaprla sredi telesa"))?
+                }
             }
-            
-            match request.parse(&buffer)? {
-                httparse::Status::Partial => continue,
-                httparse::Status::Complete(_) => break
+        }
+
+        async fn take(&mut self, length: usize) -> Result<Vec<u8>> {
+            while self.buffer.len() < length {
+                if self.fill().await? == 0 {
+                    Err(Error::malformed_request("Povezava se je zaprla sredi telesa"))?
+                }
             }
-        };
+            Ok(self.buffer.drain(..length).collect())
+        }
 
-        let mut headers = [httparse::EMPTY_HEADER; 16];
-        let mut request = httparse::Request::new(&mut headers);
-            
-        let mut remaining = match request.parse(&buffer)? {
-            httparse::Status::Partial => Err(Error::http_too_long(&BUFFER_LENGTH))?,
-            httparse::Status::Complete(offset) => buffer[offset..].to_vec()
-        };
+        async fn read_to_end(mut self) -> Result<Vec<u8>> {
+            while self.fill().await? > 0 {
+                if self.buffer.len() > MAX_BODY_LENGTH {
+                    Err(Error::http_too_long(&MAX_BODY_LENGTH))?
+                }
+            }
+            Ok(std::mem::take(self.buffer))
+        }
 
-        // preverimo če ima body
-        let content_length: usize = 
-            match request.headers.iter()
-            .find(|h| h.name.to_lowercase() == "content-length")
-            .ok_or(Error::malformed_request("Manjka content-length")) {
-                Ok(header) => from_utf8(&header.value)?.parse()?,
-                Err(_) => 0 
-        };
+        /// Bere, dokler `complete` ne sporoči, da so headerji prebrani. Če se povezava zapre (ali resetira),
+        /// preden preberemo karkoli, vrne false.
+        async fn read_head(&mut self, complete: impl Fn(&[u8]) -> Result<bool>) -> Result<bool> {
+            loop {
+                if !self.buffer.is_empty() && complete(self.buffer)? {
+                    return Ok(true);
+                }
+                if self.buffer.len() > MAX_HEAD_LENGTH {
+                    Err(Error::http_too_long(&MAX_HEAD_LENGTH))?
+                }
+                let read = match self.fill().await {
+                    Err(error) if self.buffer.is_empty() && error.kind() == ErrorKind::ConnectionReset => 0,
+                    read => read?
+                };
+                if read == 0 {
+                    if self.buffer.is_empty() { return Ok(false); }
+                    Err(Error::malformed_request("Povezava se je zaprla sredi headerjev"))?
+                }
+            }
+        }
+
+        /// Prebere body glede na `Transfer-Encoding` oz. `Content-Length`. Če nima nobenega 
+        /// in je `until_eof` nastavljen, je body vse do zaprtja povezave.
+        async fn read_body(mut self, headers: &[(String, String)], until_eof: bool) -> Result<Vec<u8>> {
+            let chunked = find_header(headers, "transfer-encoding")
+                .and_then(|encoding| encoding.rsplit(',').next())
+                .is_some_and(|encoding| encoding.trim().eq_ignore_ascii_case("chunked"));
 
-        // pridobimo preostanek
-        let pre_len = remaining.len();
-        remaining.resize(content_length, 0);
-        if pre_len < content_length {
-            stream.read_exact(&mut remaining[pre_len..]).await?;
+            if chunked {
+                self.read_chunked().await
+            } else if let Some(content_length) = find_header(headers, "content-length") {
+                let content_length: usize = content_length.trim().parse()?;
+                if content_length > MAX_BODY_LENGTH {
+                    Err(Error::http_too_long(&MAX_BODY_LENGTH))?
+                }
+                self.take(content_length).await
+            } else if until_eof {
+                self.read_to_end().await
+            } else {
+                Ok(vec![])
+            }
         }
 
-        match request.path {
-            None => Err(Error::missing_path("NULL")),
-            Some(path) => {
-                Ok((path.to_string(), remaining.clone()))
+        async fn read_chunked(&mut self) -> Result<Vec<u8>> {
+            let mut body = vec![];
+            loop {
+                let line = self.read_line().await?;
+                let size = from_utf8(&line)?.split(';').next().unwrap_or("").trim();
+                let size = usize::from_str_radix(size, 16)
+                    .map_err(|_| Error::malformed_request("Neveljavna dolžina chunk-a"))?;
+                if size == 0 { break; }
+                if size > MAX_BODY_LENGTH - body.len() {
+                    Err(Error::http_too_long(&MAX_BODY_LENGTH))?
+                }
+
+                body.extend(self.take(size).await?);
+                if !self.read_line().await?.is_empty() {
+                    Err(Error::malformed_request("Chunk se ne konča s CRLF"))?
+                }
             }
+
+            // trailerje preberemo in zavržemo, skupaj pa so lahko dolgi največ toliko kot headerji
+            let mut trailers = 0;
+            loop {
+                let line = self.read_line().await?;
+                if line.is_empty() { return Ok(body); }
+                trailers += line.len();
+                if trailers > MAX_HEAD_LENGTH {
+                    Err(Error::http_too_long(&MAX_HEAD_LENGTH))?
+                }
+            }
+        }
+    }
+
+    fn parse_error(error: httparse::Error, max_headers: usize) -> Error {
+        match error {
+            httparse::Error::TooManyHeaders => Error::too_many_headers(max_headers),
+            error => error.into()
         }
     }
 
-    /// Preberemo HTTP response, tudi če je dolg in vrnemo (reason, code, body)
-    pub async fn read_http_response(stream: &mut TcpStream) -> Result<(String, u16, Vec<u8>)> {
+    fn owned_headers(headers: &[httparse::Header]) -> Vec<(String, String)> {
+        headers.iter()
+            .map(|h| (h.name.to_owned(), String::from_utf8_lossy(h.value).into_owned()))
+            .collect()
+    }
 
-        let mut buffer = vec![];
-        
-        loop {
-            let mut headers = [httparse::EMPTY_HEADER; 16];
-            let mut response = httparse::Response::new(&mut headers);
+    /// Preberemo HTTP request, tudi če je dolg in vrnemo method, path, headerje in body.
+    /// Če odjemalec zapre povezavo, preden pošlje karkoli, vrnemo None. Bajte za koncem requesta pustimo v `buffer`.
+    pub async fn read_http_request(stream: &mut (impl AsyncRead + Unpin), buffer: &mut ReadBuffer) -> Result<Option<HttpRequest>> {
+        let max_headers = buffer.max_headers;
+        let mut reader = Reader::new(stream, buffer);
 
-            {
-                let mut buffer_tmp = [0; BUFFER_LENGTH];
-                let read_current = stream.read(&mut buffer_tmp).await?;
+        let complete = |buffer: &[u8]| {
+            let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+            let status = httparse::Request::new(&mut headers).parse(buffer).map_err(|e| parse_error(e, max_headers))?;
+            Ok(status.is_complete())
+        };
+        if !reader.read_head(complete).await? { return Ok(None); }
 
-                buffer.extend_from_slice(&buffer_tmp[..read_current]);
-            }
-            
-            match response.parse(&buffer)? {
-                httparse::Status::Partial => continue,
-                httparse::Status::Complete(_) => break
-            }
+        let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+        let mut request = httparse::Request::new(&mut headers);
+        let offset = match request.parse(reader.buffer).map_err(|e| parse_error(e, max_headers))? {
+            httparse::Status::Partial => Err(Error::http_too_long(&MAX_HEAD_LENGTH))?,
+            httparse::Status::Complete(offset) => offset
         };
 
-        let mut headers = [httparse::EMPTY_HEADER; 16];
+        let method = match request.method {
+            None => Err(Error::malformed_request("Manjka metoda"))?,
+            Some(method) => Method::from_bytes(method.as_bytes()).map_err(|_| Error::malformed_request("Neveljavna metoda"))?
+        };
+        let path = match request.path {
+            None => Err(Error::missing_path("NULL"))?,
+            Some(path) => path.to_owned()
+        };
+        let version = request.version.unwrap_or(1);
+        let headers = owned_headers(request.headers);
+        reader.buffer.drain(..offset);
+
+        // request brez Content-Length in Transfer-Encoding nima telesa
+        let body = reader.read_body(&headers, false).await?;
+        Ok(Some(HttpRequest { method, path, version, headers, body }))
+    }
+
+    /// Preberemo HTTP response, tudi če je dolg in vrnemo status, headerje in body. Bajte za koncem responsa pustimo v `buffer`.
+    pub async fn read_http_response(stream: &mut (impl AsyncRead + Unpin), buffer: &mut ReadBuffer) -> Result<HttpResponse> {
+        read_http_response_or_eof(stream, buffer).await?
+            .ok_or(Error::malformed_request("Povezava se je zaprla pred koncem headerjev"))
+    }
+
+    /// Kot `read_http_response`, le da vrne None, če strežnik zapre povezavo, preden pošlje karkoli
+    pub async fn read_http_response_or_eof(stream: &mut (impl AsyncRead + Unpin), buffer: &mut ReadBuffer) -> Result<Option<HttpResponse>> {
+        let max_headers = buffer.max_headers;
+        let mut reader = Reader::new(stream, buffer);
+
+        let complete = |buffer: &[u8]| {
+            let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
+            let status = httparse::Response::new(&mut headers).parse(buffer).map_err(|e| parse_error(e, max_headers))?;
+            Ok(status.is_complete())
+        };
+        if !reader.read_head(complete).await? { return Ok(None); }
+
+        let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
         let mut response = httparse::Response::new(&mut headers);
-            
-        let mut remaining = match response.parse(&buffer)? {
-            httparse::Status::Partial => Err(Error::http_too_long(&BUFFER_LENGTH))?,
-            httparse::Status::Complete(offset) => buffer[offset..].to_vec()
+        let offset = match response.parse(reader.buffer).map_err(|e| parse_error(e, max_headers))? {
+            httparse::Status::Partial => Err(Error::http_too_long(&MAX_HEAD_LENGTH))?,
+            httparse::Status::Complete(offset) => offset
         };
 
-        // preverimo če ima body
-        let content_length: usize = 
-            match response.headers.iter()
-            .find(|h| h.name.to_lowercase() == "content-length")
-            .ok_or(Error::malformed_request("Manjka content-length")) {
-                Ok(header) => from_utf8(&header.value)?.parse()?,
-                Err(_) => 0 
+        let (reason, status) = match (response.reason, response.code) {
+            (Some(reason), Some(status)) => (reason.to_owned(), status),
+            _ => Err(Error::http_missing_response())?
         };
+        let version = response.version.unwrap_or(1);
+        let headers = owned_headers(response.headers);
+        reader.buffer.drain(..offset);
 
-        // pridobimo preostanek
-        let pre_len = remaining.len();
-        remaining.resize(content_length, 0);
-        if pre_len < content_length {
-            stream.read_exact(&mut remaining[pre_len..]).await?;
-        }
-    
-        match (response.reason, response.code) {
-            (Some(reason), Some(code)) => Ok((reason.to_owned(), code, remaining)),
-            _ => Err(Error::http_missing_response())
-        }
+        // 1xx, 204 in 304 nimajo telesa, sicer response brez dolžine traja do zaprtja povezave
+        let until_eof = !(status < 200 || status == 204 || status == 304);
+        let body = reader.read_body(&headers, until_eof).await?;
+        Ok(Some(HttpResponse { reason, status, version, headers, body }))
     }
 }
 
 pub mod write {
+    use ::http::Method;
     use tokio::{io::AsyncWriteExt, net::TcpStream};
     use crate::error::Result;
 
     /// Pošljemo HTTP response
     pub async fn write_http(status: &str, data: &[u8], stream: &mut TcpStream) -> Result<()> {
+        write_http_response(status, "application/json", &[], data, true, stream).await
+    }
+
+    /// Pošljemo HTTP response z dodatnimi headerji. Če `with_body` ni nastavljen (npr. pri HEAD), 
+    /// pošljemo le headerje, Content-Length pa je še vedno dolžina telesa.
+    pub async fn write_http_response(status: &str, content_type: &str, headers: &[(&str, String)], data: &[u8], with_body: bool, stream: &mut TcpStream) -> Result<()> {
+
+        let mut response_start = format!("HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n", data.len());
+        for (name, value) in headers {
+            response_start.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response_start.push_str("\r\n");
+
+        stream.write_all(response_start.as_bytes()).await?;
+        if with_body { stream.write_all(data).await?; }
+
+        Ok(())
+    }
+
+    /// Pošljemo začetek HTTP responsa, katerega telo pošljemo po kosih (Transfer-Encoding: chunked)
+    pub async fn write_chunked_response_start(status: &str, content_type: &str, headers: &[(&str, String)], stream: &mut TcpStream) -> Result<()> {
 
-        let response_start = format!("HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n", data.len());
-        stream.write_all(&response_start.as_bytes()).await?;
-        stream.write_all(&data).await?;
+        let mut response_start = format!("HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nTransfer-Encoding: chunked\r\n");
+        for (name, value) in headers {
+            response_start.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response_start.push_str("\r\n");
+
+        stream.write_all(response_start.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Pošljemo en kos telesa. Praznega ne pošljemo, saj bi pomenil konec telesa.
+    pub async fn write_chunk(data: &[u8], stream: &mut TcpStream) -> Result<()> {
+        if data.is_empty() { return Ok(()); }
 
+        stream.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+        stream.write_all(data).await?;
+        stream.write_all(b"\r\n").await?;
         Ok(())
     }
 
-    /// Pošljemo HTTP POST request
-    pub async fn write_post_request(host: &str, endpoint: &str, data: &[u8], stream: &mut TcpStream) -> Result<()> {
+    /// Pošljemo zadnji (prazen) kos telesa
+    pub async fn write_chunked_end(stream: &mut TcpStream) -> Result<()> {
+        stream.write_all(b"0\r\n\r\n").await?;
+        Ok(())
+    }
+
+    /// Pošljemo HTTP request s telesom (npr. POST ali DELETE) z dodatnimi headerji
+    pub async fn write_request(method: &Method, host: &str, endpoint: &str, headers: &[(&str, String)], data: &[u8], stream: &mut TcpStream) -> Result<()> {
         
-        let response_start = format!(
-            "POST {endpoint} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n", data.len()
+        let mut response_start = format!(
+            "{method} {endpoint} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n", data.len()
         );
-        stream.write_all(&response_start.as_bytes()).await?;
-        stream.write_all(&data).await?;
+        for (name, value) in headers {
+            response_start.push_str(&format!("{name}: {value}\r\n"));
+        }
+        response_start.push_str("\r\n");
+        stream.write_all(response_start.as_bytes()).await?;
+        stream.write_all(data).await?;
         
         Ok(())
     }
@@ -151,8 +458,187 @@ pub mod write {
         let response_start = format!(
             "GET {endpoint} HTTP/1.1\r\nHost: {host}\r\n\r\n"
         );
-        stream.write_all(&response_start.as_bytes()).await?;        
+        stream.write_all(response_start.as_bytes()).await?;        
         Ok(())
 
     }
-}
\ No newline at end of file
+}
+
+pub mod route {
+    use ::http::Method;
+    use tokio::net::TcpStream;
+    use crate::error::{Error, Result};
+    use super::{helper::allow_header, read::HttpRequest, write::write_http_response};
+
+    /// Preveri, ali pot podpira metodo requesta. Na OPTIONS odgovori sama in vrne None,
+    /// za HEAD pa vrne GET (odgovor nato pošljemo brez telesa).
+    pub async fn resolve_method(request: &HttpRequest, path: &str, allowed: &[Method], stream: &mut TcpStream) -> Result<Option<Method>> {
+        if request.method == Method::OPTIONS {
+            write_http_response("204 No Content", "application/json", &[("Allow", allow_header(allowed))], &[], true, stream).await?;
+            Ok(None)
+        } else if request.method == Method::HEAD && allowed.contains(&Method::GET) {
+            Ok(Some(Method::GET))
+        } else if allowed.contains(&request.method) {
+            Ok(Some(request.method.clone()))
+        } else {
+            Err(Error::method_not_allowed(request.method.as_str(), path, &allow_header(allowed)))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use crate::{error::ErrorType, parse::remote::Remote};
+    use super::{helper::split_query, read::{read_http_request, read_http_response, ReadBuffer}, write::write_http_response};
+
+    #[tokio::test]
+    async fn test_chunked() {
+        let mut request: &[u8] = b"POST /sequence/const HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\n{\"a\"\r\n3\r\n: 1\r\n1\r\n}\r\n0\r\nTrailer: x\r\n\r\n";
+        let request = read_http_request(&mut request, &mut ReadBuffer::default()).await.unwrap().unwrap();
+        assert_eq!((request.path.as_str(), request.body.as_slice()), ("/sequence/const", b"{\"a\": 1}".as_slice()));
+
+        let mut response: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\nA\r\n0123456789\r\n0\r\n\r\n";
+        assert_eq!(read_http_response(&mut response, &mut ReadBuffer::default()).await.unwrap().body, b"0123456789");
+
+        let mut response: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert_eq!(read_http_response(&mut response, &mut ReadBuffer::default()).await.unwrap_err().error_type(), &ErrorType::HttpParseError);
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let too_long = |message: String| async move {
+            let error = read_http_request(&mut message.as_bytes(), &mut ReadBuffer::default()).await.unwrap_err();
+            error.error_type() == &ErrorType::HttpRequestTooShort
+        };
+        let chunked = "POST /sequence/const HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+        // predolga vrstica z dolžino chunk-a, prevelik chunk, predolgi trailerji in prevelik Content-Length
+        assert!(too_long(format!("{chunked}1{}", ";".repeat(20_000))).await);
+        assert!(too_long(format!("{chunked}FFFFFFFFFF\r\n")).await);
+        assert!(too_long(format!("{chunked}0\r\n{}", "X: 1\r\n".repeat(20_000))).await);
+        assert!(too_long("POST /sequence/const HTTP/1.1\r\nContent-Length: 1000000000000\r\n\r\n".to_owned()).await);
+    }
+
+    #[tokio::test]
+    async fn test_body_length() {
+        // response brez dolžine traja do konca povezave, request pa nima telesa
+        let mut response: &[u8] = b"HTTP/1.0 200 OK\r\n\r\n[1, 2]";
+        assert_eq!(read_http_response(&mut response, &mut ReadBuffer::default()).await.unwrap().body, b"[1, 2]");
+
+        let mut response: &[u8] = b"HTTP/1.1 204 No Content\r\n\r\n";
+        assert_eq!(read_http_response(&mut response, &mut ReadBuffer::default()).await.unwrap().body, b"");
+
+        let mut request: &[u8] = b"GET /ping HTTP/1.1\r\n\r\n";
+        assert_eq!(read_http_request(&mut request, &mut ReadBuffer::default()).await.unwrap().unwrap().body, b"");
+
+        let mut request: &[u8] = b"POST /sequence/const HTTP/1.1\r\nContent-Length: 10\r\n\r\n[1, 2]";
+        assert_eq!(read_http_request(&mut request, &mut ReadBuffer::default()).await.unwrap_err().error_type(), &ErrorType::HttpParseError);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined() {
+        // requesti, poslani naenkrat, se preberejo po vrsti, saj bajti za prvim ostanejo v bufferju povezave
+        let mut requests: &[u8] = b"POST /query HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}GET /ping HTTP/1.1\r\n\r\nGET /cache HTTP/1.1\r\n\r\n";
+        let mut buffer = ReadBuffer::default();
+        let mut paths = vec![];
+        while let Some(request) = read_http_request(&mut requests, &mut buffer).await.unwrap() {
+            paths.push(request.path);
+        }
+        assert_eq!(paths, vec!["/query", "/ping", "/cache"]);
+
+        let mut responses: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n[1]HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n0\r\n\r\n";
+        let mut buffer = ReadBuffer::default();
+        assert_eq!(read_http_response(&mut responses, &mut buffer).await.unwrap().body, b"[1]");
+        let response = read_http_response(&mut responses, &mut buffer).await.unwrap();
+        assert_eq!((response.status, response.body.as_slice()), (404, b"{}".as_slice()));
+    }
+
+    #[test]
+    fn test_split_query() {
+        assert_eq!(split_query("/sequences"), ("/sequences", vec![]));
+        assert_eq!(
+            split_query("/sequences/?name=linear_rec_h&parameters=6&&opis=a+b%2Cc%"),
+            ("/sequences/", vec![
+                ("name".to_owned(), "linear_rec_h".to_owned()),
+                ("parameters".to_owned(), "6".to_owned()),
+                ("opis".to_owned(), "a b,c%".to_owned())
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eof_and_headers() {
+        let mut empty: &[u8] = b"";
+        assert!(read_http_request(&mut empty, &mut ReadBuffer::default()).await.unwrap().is_none());
+
+        let mut request: &[u8] = b"GET /ping HTTP/1.1\r\nHost: a";
+        assert_eq!(read_http_request(&mut request, &mut ReadBuffer::default()).await.unwrap_err().error_type(), &ErrorType::HttpParseError);
+
+        let mut response: &[u8] = b"";
+        assert!(read_http_response(&mut response, &mut ReadBuffer::default()).await.is_err());
+
+        let many = format!("GET /ping HTTP/1.1\r\n{}\r\n", "X-Header: 1\r\n".repeat(65));
+        assert_eq!(read_http_request(&mut many.as_bytes(), &mut ReadBuffer::default()).await.unwrap_err().error_type(), &ErrorType::TooManyHeaders);
+        assert!(read_http_request(&mut many.as_bytes(), &mut ReadBuffer::new(100)).await.unwrap().is_some());
+
+        let error = read_http_request(&mut "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n".as_bytes(), &mut ReadBuffer::new(1)).await.unwrap_err();
+        assert_eq!(serde_json::to_value(&error).unwrap()["extra"]["max_headers"], 1);
+        assert_eq!(error.status(), ::http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = Remote::new("test", "127.0.0.1", listener.local_addr().unwrap().port()).unwrap();
+
+        // strežnik sprejme samo eno povezavo, zato morata oba requesta iti po njej
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (mut buffer, mut paths) = (ReadBuffer::default(), vec![]);
+            while let Some(request) = read_http_request(&mut stream, &mut buffer).await.unwrap() {
+                write_http_response("200 OK", "application/json", &[request.connection_header()], request.path.as_bytes(), true, &mut stream).await.unwrap();
+                paths.push(request.path);
+            }
+            paths
+        });
+
+        assert_eq!(remote.expect_success(remote.get("/prvi", None).await.unwrap()), Ok(b"/prvi".to_vec()));
+        assert_eq!(remote.expect_success(remote.post("/drugi", b"{}", None).await.unwrap()), Ok(b"/drugi".to_vec()));
+
+        // zapremo povezave v pool-u, da strežnik prebere EOF
+        drop(remote.connect().await);
+        while let Some(stream) = remote.take_pooled_stream() { drop(stream) }
+        assert_eq!(server.await.unwrap(), vec!["/prvi".to_owned(), "/drugi".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn test_pooled_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = Remote::new("test", "127.0.0.1", listener.local_addr().unwrap().port()).unwrap();
+
+        // strežnik na vsako povezavo odgovori le enkrat: prvič pravilno (nato povezavo zapre),
+        // drugič pa zapre povezavo sredi responsa
+        let server = tokio::spawn(async move {
+            let mut paths = vec![];
+            for response in [&b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}"[..], b"HTTP/1.1 200 OK\r\nContent-Le"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_http_request(&mut stream, &mut ReadBuffer::default()).await.unwrap().unwrap();
+                paths.push(request.path);
+                stream.write_all(response).await.unwrap();
+            }
+            if let Ok(Ok((mut stream, _))) = tokio::time::timeout(std::time::Duration::from_millis(500), listener.accept()).await {
+                if let Ok(Some(request)) = read_http_request(&mut stream, &mut ReadBuffer::default()).await { paths.push(request.path); }
+            }
+            paths
+        });
+
+        assert!(remote.get("/prvi", None).await.is_ok());
+        // povezava v pool-u je zaprta, preden remote karkoli odgovori, zato request ponovimo na novi povezavi,
+        // ta pa se zapre sredi responsa, zato request ne ponovimo, saj ga je remote morda že izvedel
+        assert!(remote.post("/drugi", b"{}", None).await.is_err());
+        assert_eq!(server.await.unwrap(), vec!["/prvi".to_owned(), "/drugi".to_owned()]);
+    }
+}
@@ -4,10 +4,11 @@ pub mod sequnce_providers;
 pub mod error;
 pub mod http;
 pub mod parse;
+pub mod registry;
 
 /* ---------- Vsebuje splošne pomagalne funkcije ---------- */
 
-#[derive(PartialOrd, Clone)]
+#[derive(Clone)]
 pub struct OrderableF64(f64);
 
 impl PartialEq for OrderableF64 {
@@ -16,4 +17,5 @@ impl PartialEq for OrderableF64 {
     }
 }
 impl Eq for OrderableF64 {}
+impl PartialOrd for OrderableF64 { fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) } }
 impl Ord for OrderableF64 { fn cmp(&self, other: &Self) -> std::cmp::Ordering { self.0.total_cmp(&other.0) } }
\ No newline at end of file
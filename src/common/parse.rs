@@ -1,6 +1,7 @@
 pub mod settings {
-    use std::net::{IpAddr, Ipv4Addr};
-    use clap::Parser;
+    use std::{net::{IpAddr, Ipv4Addr}, path::PathBuf};
+    use clap::{Parser, Subcommand, ValueEnum};
+    use super::sequence_provide::Domain;
 
     #[derive(Parser, Debug)]
     #[command(name = "FloatTorrent ponudnik zaporedij", version, about, long_about=None)]
@@ -25,13 +26,77 @@ pub mod settings {
         #[arg(short, long, default_value_t=9000)]
         pub port:    u16,
 
-        /// Želen čas v sekundah, po katerem se ponudnik znova posvetuje z centralnim in pridobi zaporedja, ki jih ponujajo drugi. 
+        /// Želen čas v sekundah, po katerem se ponudnik znova registrira pri centralnem in pridobi zaporedja, ki jih ponujajo drugi. 
         #[arg(long, default_value_t=60)]
         pub osvezitveni_cas:  u64,
 
         /// Maksimalna globina, za katero obstajajo zaporedja lin_rec_h
         #[arg(long, default_value_t=4)]
-        pub lin_recur_globina:  u8
+        pub lin_recur_globina:  u8,
+
+        /// Stopnja polinomov, ki so koeficienti zaporedij p_recursive (teh je prav tako do globine lin_recur_globina)
+        #[arg(long, default_value_t=2)]
+        pub p_rec_stopnja:  u8,
+
+        /// Največje število generatorjev, skozi katere se lahko posreduje zahteva za zaporedje
+        #[arg(long, default_value_t=8)]
+        pub max_skokov: usize,
+
+        /// Največje število oddaljenih ponudnikov, ki jih poskusimo, preden zahteva za zaporedje ne uspe
+        #[arg(long, default_value_t=3)]
+        pub poskusi: usize,
+
+        /// Čas v sekundah, po katerem se en poskus pridobivanja zaporedja od oddaljenega ponudnika izteče
+        #[arg(long, default_value_t=10)]
+        pub timeout_remote: u64,
+
+        /// Število zaporednih napak oddaljenega generatorja, po katerem ga začasno ne uporabljamo več
+        #[arg(long, default_value_t=3)]
+        pub prag_napak: u32,
+
+        /// Čas v sekundah, za katerega izločimo oddaljen generator, ki je presegel prag napak
+        #[arg(long, default_value_t=30)]
+        pub cas_izlocitve: u64,
+
+        /// Največje število podzaporedij, ki jih pri sestavljenih zaporedjih pridobivamo hkrati
+        #[arg(long, default_value_t=8)]
+        pub vzporedne_zahteve: usize,
+
+        /// Največje število členov, ki jih lahko vrne (ali zahteva) ena zahteva za zaporedje
+        #[arg(long, default_value_t=1_000_000)]
+        pub max_clenov: u64,
+
+        /// Največja ocenjena cena (približno število operacij) izračuna enega zaporedja
+        #[arg(long, default_value_t=100_000_000)]
+        pub max_cena: u64,
+
+        /// Največje ocenjeno število bitov enega člena pri računanju v domenah `Integer` in `Rational`
+        #[arg(long, default_value_t=1 << 22)]
+        pub max_bitov: u64,
+
+        /// Največji čas v sekundah, v katerem mora biti zahteva za zaporedje izračunana
+        #[arg(long, default_value_t=30)]
+        pub rok: u64,
+
+        /// Največje število rezultatov, ki jih hrani cache zaporedij (0 ga izklopi)
+        #[arg(long, default_value_t=256)]
+        pub cache_velikost: usize,
+
+        /// Čas v sekundah, po katerem rezultat v cache-u zastara
+        #[arg(long, default_value_t=30)]
+        pub cache_ttl: u64,
+
+        /// Največje število headerjev, ki jih sprejmemo v HTTP requestu na eni povezavi
+        #[arg(long, default_value_t=64)]
+        pub max_headerjev: usize,
+
+        /// Najmanjše število členov, pri katerem zaporedje pošiljamo po kosih, medtem ko ga računamo
+        #[arg(long, default_value_t=10_000)]
+        pub prag_pretakanja: u64,
+
+        /// Žeton za administrativne poti (`Authorization: Bearer <žeton>`). Brez njega so dovoljene le zahteve z loopback naslova
+        #[arg(long)]
+        pub admin_zeton: Option<String>
     }
 
     #[derive(Parser, Debug)]
@@ -55,19 +120,128 @@ pub mod settings {
 
         /// Čas, v sekundah, po katerem se ping izteče
         #[arg(short, default_value_t=5)]
-        pub timeout_ping:  u64
+        pub timeout_ping:  u64,
+
+        /// Število zaporednih zgrešenih pingov, po katerem generator odstranimo iz registra
+        #[arg(long, default_value_t=3)]
+        pub zgreseni_pingi: u32,
+
+        /// Datoteka, v kateri hranimo register generatorjev, da ga ob ponovnem zagonu ne izgubimo
+        #[arg(long, default_value = "register.json")]
+        pub datoteka_registra: PathBuf,
+
+        /// Največje število headerjev, ki jih sprejmemo v HTTP requestu na eni povezavi
+        #[arg(long, default_value_t=64)]
+        pub max_headerjev: usize
+    }
+
+    #[derive(Parser, Debug)]
+    #[command(name = "FloatTorrent odjemalec", version, about, long_about=None)]
+    pub struct SettingsOdjemalec {
+        /// Oblika izpisa
+        #[arg(long, value_enum, default_value_t=Oblika::Tabela)]
+        pub oblika: Oblika,
+
+        /// Čas v sekundah, po katerem se zahteva izteče
+        #[arg(long, default_value_t=30)]
+        pub timeout: u64,
+
+        #[command(subcommand)]
+        pub ukaz: Ukaz
+    }
+
+    #[derive(Subcommand, Debug)]
+    pub enum Ukaz {
+        /// Izpiše generatorje, registrirane na centralnem strežniku
+        Generatorji {
+            /// IP naslov centralnega strežnika
+            #[arg(long, default_value_t=IpAddr::V4(Ipv4Addr::new(127,0,0,1)))]
+            centralni_ip: IpAddr,
+
+            /// Port centralnega strežnika
+            #[arg(long, default_value_t=9999)]
+            centralni_port: u16,
+
+            /// Endpoint centralnega strežnika, na katerem so registrirani generatorji
+            #[arg(long, default_value_t=String::from("/project"))]
+            register_endpoint: String
+        },
+        /// Izpiše zaporedja, ki jih ponuja generator
+        Zaporedja {
+            /// IP naslov generatorja
+            #[arg(short, long, default_value_t=IpAddr::V4(Ipv4Addr::new(127,0,0,1)))]
+            ip: IpAddr,
+
+            /// Port generatorja
+            #[arg(short, long, default_value_t=9000)]
+            port: u16
+        },
+        /// Izračuna člene zaporedja, podanega z izrazom, npr. `sum(arithmetic(0,2), drop[3](geometric(1,2)))`
+        Cleni {
+            /// Zaporedje, zapisano kot `ime[parametri](zaporedja)`
+            izraz: String,
+
+            /// IP naslov generatorja
+            #[arg(short, long, default_value_t=IpAddr::V4(Ipv4Addr::new(127,0,0,1)))]
+            ip: IpAddr,
+
+            /// Port generatorja
+            #[arg(short, long, default_value_t=9000)]
+            port: u16,
+
+            /// Indeks prvega člena
+            #[arg(long, default_value_t=0)]
+            od: u64,
+
+            /// Indeks, pred katerim se zaporedje konča
+            #[arg(long, default_value_t=10)]
+            r#do: u64,
+
+            /// Korak med indeksi
+            #[arg(long, default_value_t=1)]
+            korak: u64,
+
+            /// Domena, v kateri generator računa člene
+            #[arg(long, value_enum, default_value_t=Domain::Float)]
+            domena: Domain
+        }
+    }
+
+    #[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+    pub enum Oblika {
+        Tabela,
+        Csv,
+        Json
     }
 }    
 
 pub mod remote {
+    use std::collections::HashMap;
     use std::net::IpAddr;
+    use std::sync::{Mutex, OnceLock};
     use std::time::Duration;
 
+    use ::http::Method;
     use serde::{Deserialize, Serialize};
     use tokio::net::TcpStream;
     use tokio::time::timeout;
     
-    use crate::{error::{Error, Result}, http};
+    use crate::{error::{Error, Result}, http::{self, read::{HttpResponse, ReadBuffer}}};
+
+    /// Največje število odprtih povezav, ki jih hranimo za posamezen Remote
+    const POOL_SIZE: usize = 4;
+
+    /// Odprta povezava do remote-a skupaj z bajti, ki smo jih z nje prebrali, a še niso del nobenega responsa
+    pub struct Connection {
+        pub stream: TcpStream,
+        pub buffer: ReadBuffer
+    }
+
+    /// Odprte povezave do remote-ov, ki jih lahko ponovno uporabimo (po url-ju)
+    fn pool() -> &'static Mutex<HashMap<String, Vec<Connection>>> {
+        static POOL: OnceLock<Mutex<HashMap<String, Vec<Connection>>>> = OnceLock::new();
+        POOL.get_or_init(|| Mutex::new(HashMap::new()))
+    }
 
     /// Strukutra predstavlja nekaj kar ima HTTP endpointe
     #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
@@ -91,43 +265,105 @@ pub mod remote {
             })
         }
 
-        pub async fn get_stream(&self) -> Result<TcpStream> {
-            Ok(TcpStream::connect(&self.get_url()).await?)
+        pub async fn connect(&self) -> Result<Connection> {
+            Ok(Connection { stream: TcpStream::connect(&self.get_url()).await?, buffer: ReadBuffer::default() })
         }
 
-        /// Naredi GET request, če stream ni podan ga odpre
-        pub async fn get(&self, endpoint: &str, stream: Option<&mut TcpStream>) -> Result<(String, u16, Vec<u8>)> {
-            let stream: &mut TcpStream = match stream {
-                None => &mut (self.get_stream().await?),
-                Some(stream) => stream
-            };
-            http::write::write_get_request(&self.get_url(), endpoint, stream).await?;
-            http::read::read_http_response(stream).await
+        pub(crate) fn take_pooled_stream(&self) -> Option<Connection> {
+            pool().lock().unwrap().get_mut(&self.get_url())?.pop()
         }
 
-        /// Naredi POST request, če stream ni podan ga odpre
-        pub async fn post(&self, endpoint: &str, data: &[u8], stream: Option<&mut TcpStream>) -> Result<(String, u16, Vec<u8>)> {
-            let stream: &mut TcpStream = match stream {
-                None => &mut (self.get_stream().await?),
-                Some(stream) => stream
-            };
-            http::write::write_post_request(&self.get_url(), endpoint, data, stream).await?;
-            http::read::read_http_response(stream).await
-        }
-        /// Pinga z podamim timeoutom, če stream ni podan ga odpre.
-        pub async fn ping(&self, stream: Option<&mut TcpStream>, timeout_length: u64) -> Result<()> {
-            let (reason, status, data) = timeout(Duration::from_secs(timeout_length), self.get("/ping/", stream)).await??;
-            if (reason, status) == ("OK".to_owned(), 200) { Ok(()) }
-            else {Err(Error::remote_invalid_response(&self.get_url(), &data))}
+        fn return_pooled_stream(&self, connection: Connection) {
+            let mut pool = pool().lock().unwrap();
+            let connections = pool.entry(self.get_url()).or_default();
+            if connections.len() < POOL_SIZE { connections.push(connection); }
+        }
+
+        // pošlje request s podano metodo (GET brez telesa)
+        async fn write(&self, method: &Method, endpoint: &str, headers: &[(&str, String)], data: &[u8], connection: &mut Connection) -> Result<()> {
+            if method == Method::GET {
+                http::write::write_get_request(&self.get_url(), endpoint, &mut connection.stream).await
+            } else {
+                http::write::write_request(method, &self.get_url(), endpoint, headers, data, &mut connection.stream).await
+            }
+        }
+
+        // pošlje request in prebere response
+        async fn send(&self, method: &Method, endpoint: &str, headers: &[(&str, String)], data: &[u8], connection: &mut Connection) -> Result<HttpResponse> {
+            self.write(method, endpoint, headers, data, connection).await?;
+            http::read::read_http_response(&mut connection.stream, &mut connection.buffer).await
+        }
+
+        /// Pošlje request po odprti povezavi iz pool-a, če ta obstaja, sicer odpre novo.
+        /// Če remote povezave ne zapre, jo vrnemo v pool.
+        async fn send_pooled(&self, method: &Method, endpoint: &str, headers: &[(&str, String)], data: &[u8]) -> Result<HttpResponse> {
+            if let Some(mut connection) = self.take_pooled_stream() {
+                // remote je morda medtem zaprl povezavo. Request ponovimo na novi povezavi le, če ga nismo mogli poslati ali
+                // če je remote povezavo zaprl, ne da bi karkoli odgovoril. Sicer ga je morda že izvedel (POST, DELETE).
+                if self.write(method, endpoint, headers, data, &mut connection).await.is_ok() {
+                    if let Some(response) = http::read::read_http_response_or_eof(&mut connection.stream, &mut connection.buffer).await? {
+                        if response.keep_alive() { self.return_pooled_stream(connection); }
+                        return Ok(response);
+                    }
+                }
+            }
+
+            let mut connection = self.connect().await?;
+            let response = self.send(method, endpoint, headers, data, &mut connection).await?;
+            if response.keep_alive() { self.return_pooled_stream(connection); }
+            Ok(response)
+        }
+
+        // pošlje request po podani povezavi ali pa po povezavi iz pool-a
+        async fn request(&self, method: Method, endpoint: &str, headers: &[(&str, String)], data: &[u8], connection: Option<&mut Connection>) -> Result<HttpResponse> {
+            match connection {
+                None => self.send_pooled(&method, endpoint, headers, data).await,
+                Some(connection) => self.send(&method, endpoint, headers, data, connection).await
+            }
+        }
+
+        /// Naredi GET request, če povezava ni podana, uporabi povezavo iz pool-a
+        pub async fn get(&self, endpoint: &str, connection: Option<&mut Connection>) -> Result<HttpResponse> {
+            self.request(Method::GET, endpoint, &[], &[], connection).await
+        }
+
+        /// Naredi POST request, če povezava ni podana, uporabi povezavo iz pool-a
+        pub async fn post(&self, endpoint: &str, data: &[u8], connection: Option<&mut Connection>) -> Result<HttpResponse> {
+            self.post_with_headers(endpoint, &[], data, connection).await
+        }
+
+        /// Naredi POST request z dodatnimi headerji, če povezava ni podana, uporabi povezavo iz pool-a
+        pub async fn post_with_headers(&self, endpoint: &str, headers: &[(&str, String)], data: &[u8], connection: Option<&mut Connection>) -> Result<HttpResponse> {
+            self.request(Method::POST, endpoint, headers, data, connection).await
+        }
+
+        /// Naredi DELETE request s telesom, če povezava ni podana, uporabi povezavo iz pool-a
+        pub async fn delete(&self, endpoint: &str, data: &[u8], connection: Option<&mut Connection>) -> Result<HttpResponse> {
+            self.request(Method::DELETE, endpoint, &[], data, connection).await
+        }
+
+        /// Vrne telo response-a, če je ta uspešen (status 2xx), sicer napako, ki vsebuje status
+        pub fn expect_success(&self, response: HttpResponse) -> Result<Vec<u8>> {
+            if (200..300).contains(&response.status) { Ok(response.body) }
+            else { Err(Error::remote_invalid_response(&self.get_url(), response.status, &response.body)) }
+        }
+
+        /// Pinga z podamim timeoutom, če povezava ni podana, uporabi povezavo iz pool-a.
+        pub async fn ping(&self, connection: Option<&mut Connection>, timeout_length: u64) -> Result<()> {
+            let response = timeout(Duration::from_secs(timeout_length), self.get("/ping/", connection)).await??;
+            self.expect_success(response).map(|_| ())
         } 
     }
 }
 
 /// Strukutre za parsiranje requestov za zaporedja in responsov
 pub mod sequence_provide {        
+    use std::time::Instant;
+
+    use clap::ValueEnum;
     use serde::{Deserialize, Serialize};
     use crate::{error::Result, error::Error};
-    use super::parse_helper::Sendable;
+    use super::{parse_helper::Sendable, remote::Remote};
 
     #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
     pub struct Range {
@@ -136,6 +372,17 @@ pub mod sequence_provide {
         pub step:   u64
     }
 
+    impl Range {
+        /// Število členov v range-u (predpostavimo, da je veljaven)
+        pub fn len(&self) -> u64 {
+            if self.from >= self.to { 0 } else { (self.to - self.from - 1) / self.step + 1 }
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
     #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct SequenceParameter {
         pub name: String, 
@@ -157,11 +404,42 @@ pub mod sequence_provide {
         }
     }
 
-    #[derive(Serialize, Deserialize, Debug)]
+    /// Številska domena, v kateri računamo člene zaporedja
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Domain {
+        /// Števila s plavajočo vejico (`f64`)
+        #[default]
+        Float,
+        /// Točna cela števila poljubne velikosti
+        Integer,
+        /// Točna racionalna števila poljubne velikosti
+        Rational
+    }
+
+    impl Domain {
+        pub fn is_float(&self) -> bool {
+            *self == Domain::Float
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct Request {
         pub range: Range,
         pub parameters: Vec<f64>,
-        pub sequences: Vec<SequenceParameter>
+        pub sequences: Vec<SequenceParameter>,
+        /// Domena, v kateri računamo člene. Člene točnih domen pošljemo kot JSON nize.
+        #[serde(default, skip_serializing_if = "Domain::is_float")]
+        pub domain: Domain,
+        /// Naslovi generatorjev, ki so zahtevo že posredovali naprej
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub visited: Vec<String>,
+        /// Preostali čas v milisekundah, v katerem mora biti zahteva izračunana
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub deadline_ms: Option<u64>,
+        /// Trenutek, do katerega mora biti zahteva izračunana, se ne pošilja
+        #[serde(skip)]
+        pub deadline: Option<Instant>
     }
     impl Sendable for Request {}
 
@@ -181,6 +459,19 @@ pub mod sequence_provide {
                 description: "".to_owned()
             }
         }
+
+        /// Ustvari request za podzaporedje, ki ohrani pot posredovanja
+        pub fn sub_request(&self, sequence: &SequenceParameter, range: Range) -> Request {
+            Request {
+                range,
+                parameters: sequence.parameters.clone(),
+                sequences: sequence.sequences.clone(),
+                domain: self.domain,
+                visited: self.visited.clone(),
+                deadline_ms: self.deadline_ms,
+                deadline: self.deadline
+            }
+        }
     }
 
     impl SequenceParameter {
@@ -194,12 +485,471 @@ pub mod sequence_provide {
         } 
     }
 
+    /// Generator, ki ponuja zaporedje iz kataloga centralnega strežnika
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct CatalogProvider {
+        #[serde(flatten)]
+        pub remote: Remote,
+        /// Zapisi, ki jih generator oglašuje v headerju `X-Sequence-Encodings`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub encodings: Option<String>
+    }
+
+    /// Zaporedje iz kataloga centralnega strežnika (`GET /sequences`) z vsemi generatorji, ki ga ponujajo
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct CatalogEntry {
+        #[serde(flatten)]
+        pub info: SequenceInfo,
+        pub providers: Vec<CatalogProvider>
+    }
+
+    /// Zahteva za zaporedje, zapisano z izrazom (glej `parse::expression`)
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct Query {
+        pub expression: String,
+        pub range: Range,
+        #[serde(default, skip_serializing_if = "Domain::is_float")]
+        pub domain: Domain,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub deadline_ms: Option<u64>
+    }
+
+    impl Query {
+        /// Vrne ime zaporedja na vrhu izraza in pripadajoč veljaven Request
+        pub fn into_request(self) -> Result<(String, Request)> {
+            let sequence = super::expression::parse_expression(&self.expression)?;
+            let request = Request {
+                range: self.range,
+                parameters: sequence.parameters,
+                sequences: sequence.sequences,
+                domain: self.domain,
+                visited: vec![],
+                deadline_ms: self.deadline_ms,
+                deadline: None
+            };
+            Ok((sequence.name, request.validate()?))
+        }
+    }
+
+    /// Definicija zaporedja s formulo, ki ga registriramo na `POST /admin/sequence`
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct FormulaDefinition {
+        pub name: String,
+        pub formula: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub parameters: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub description: Option<String>
+    }
+
+    /// Člen, podan kot JSON število ali kot niz s točno vrednostjo, npr. `"354224848179261915075"` ali `"-3/4"`
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(untagged)]
+    pub enum Term {
+        Number(f64),
+        Exact(String)
+    }
+
+    /// Členi, za katere na `POST /analyze/recurrence` iščemo najkrajšo linearno rekurzijo
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct RecurrenceQuery {
+        pub terms: Vec<Term>,
+        /// Praštevilo, po modulu katerega iščemo rekurzijo. Brez njega jo iščemo nad racionalnimi števili.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub modulus: Option<u64>
+    }
+
+    pub fn parse_query(data: &[u8]) -> Result<(String, Request)> {
+        let query: Query = serde_json::from_slice(data)?;
+        query.into_request()
+    }
+
     pub fn parse_request(data: &[u8]) -> Result<Request> { 
-        let request: Request = serde_json::from_slice(&data)?;
+        let request: Request = serde_json::from_slice(data)?;
         request.validate()
     }
 }
 
+/// Besedilni zapis sestavljenih zaporedij, npr. `sum(arithmetic(0,2), drop[3](geometric(1,2)))`
+pub mod expression {
+    use std::fmt;
+
+    use crate::error::{Error, Result};
+    use super::sequence_provide::SequenceParameter;
+
+    /// Argument v oklepajih je lahko število (parameter) ali zaporedje
+    enum Argument {
+        Number(f64),
+        Sequence(SequenceParameter)
+    }
+
+    struct Parser<'a> {
+        expression: &'a str,
+        chars:      Vec<char>,
+        position:   usize
+    }
+
+    impl<'a> Parser<'a> {
+        fn error(&self, reason: &str) -> Error {
+            Error::invalid_expression(self.expression, self.position + 1, reason)
+        }
+
+        fn peek(&self) -> Option<char> {
+            self.chars.get(self.position).copied()
+        }
+
+        fn skip_whitespace(&mut self) {
+            while self.peek().is_some_and(char::is_whitespace) { self.position += 1; }
+        }
+
+        // preskoči presledke in, če je naslednji znak `c`, še njega
+        fn eat(&mut self, c: char) -> bool {
+            self.skip_whitespace();
+            if self.peek() == Some(c) { self.position += 1; true } else { false }
+        }
+
+        fn name(&mut self) -> Result<String> {
+            self.skip_whitespace();
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') { self.position += 1; }
+
+            if start == self.position || self.chars[start].is_ascii_digit() {
+                self.position = start;
+                Err(self.error("Pričakovano ime zaporedja"))
+            } else {
+                Ok(self.chars[start..self.position].iter().collect())
+            }
+        }
+
+        fn is_number_start(&mut self) -> bool {
+            self.skip_whitespace();
+            self.peek().is_some_and(|c| c.is_ascii_digit() || c == '-' || c == '+' || c == '.')
+        }
+
+        fn number(&mut self) -> Result<f64> {
+            self.skip_whitespace();
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+') {
+                // predznak je lahko le na začetku ali za eksponentom
+                let c = self.chars[self.position];
+                if (c == '-' || c == '+') && self.position > start && !matches!(self.chars[self.position - 1], 'e' | 'E') { break; }
+                self.position += 1;
+            }
+
+            let number: String = self.chars[start..self.position].iter().collect();
+            match number.parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(number),
+                _ => {
+                    self.position = start;
+                    Err(self.error("Neveljavno število"))
+                }
+            }
+        }
+
+        // seznam elementov, ločenih z vejico, do zaključnega znaka `close`
+        fn list<T>(&mut self, close: char, mut element: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+            let mut result = vec![];
+            if self.eat(close) { return Ok(result); }
+            loop {
+                result.push(element(self)?);
+                if self.eat(close) { return Ok(result); }
+                if !self.eat(',') { return Err(self.error(&format!("Pričakovan ',' ali '{close}'"))); }
+            }
+        }
+
+        fn sequence(&mut self) -> Result<SequenceParameter> {
+            let name = self.name()?;
+
+            let bracketed = self.eat('[');
+            let mut parameters = if bracketed { self.list(']', Self::number)? } else { vec![] };
+
+            let mut sequences = vec![];
+            if self.eat('(') {
+                let arguments = self.list(')', |parser| {
+                    if parser.is_number_start() {
+                        if bracketed { return Err(parser.error("Parametri so že podani v []")); }
+                        parser.number().map(Argument::Number)
+                    } else {
+                        parser.sequence().map(Argument::Sequence)
+                    }
+                })?;
+
+                for argument in arguments {
+                    match argument {
+                        Argument::Number(number) => parameters.push(number),
+                        Argument::Sequence(sequence) => sequences.push(sequence)
+                    }
+                }
+            }
+
+            Ok(SequenceParameter { name, parameters, sequences })
+        }
+    }
+
+    /// Prebere izraz oblike `ime[parametri](zaporedja)`. Oglati oklepaji so neobvezni: brez njih so 
+    /// števila v okroglih oklepajih parametri, ostalo pa zaporedja, npr. `lin_com(2, 3, const(1), arithmetic(0, 1))`.
+    pub fn parse_expression(expression: &str) -> Result<SequenceParameter> {
+        let mut parser = Parser { expression, chars: expression.chars().collect(), position: 0 };
+        let sequence = parser.sequence()?;
+
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            return Err(parser.error("Pričakovan konec izraza"));
+        }
+        Ok(sequence)
+    }
+
+    fn write_list<T>(f: &mut fmt::Formatter<'_>, list: &[T], write: impl Fn(&mut fmt::Formatter<'_>, &T) -> fmt::Result) -> fmt::Result {
+        for (i, element) in list.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write(f, element)?;
+        }
+        Ok(())
+    }
+
+    /// Zapiše zaporedje v najkrajši obliki, ki jo `parse_expression` prebere nazaj
+    impl fmt::Display for SequenceParameter {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.name)?;
+            match (self.parameters.is_empty(), self.sequences.is_empty()) {
+                (true, true) => Ok(()),
+                (false, true) => {
+                    write!(f, "(")?;
+                    write_list(f, &self.parameters, |f, p| write!(f, "{p}"))?;
+                    write!(f, ")")
+                },
+                (parameters_empty, false) => {
+                    if !parameters_empty {
+                        write!(f, "[")?;
+                        write_list(f, &self.parameters, |f, p| write!(f, "{p}"))?;
+                        write!(f, "]")?;
+                    }
+                    write!(f, "(")?;
+                    write_list(f, &self.sequences, |f, s| write!(f, "{s}"))?;
+                    write!(f, ")")
+                }
+            }
+        }
+    }
+}
+
+/// Zapis členov zaporedja v telesu response-a in izbira zapisa glede na header Accept
+pub mod encoding {
+    use crate::error::{Error, Result};
+
+    /// Header, v katerem ponudnik na `GET /sequence/` našteje zapise, ki jih podpira
+    pub const ENCODINGS_HEADER: &str = "X-Sequence-Encodings";
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Encoding {
+        /// JSON seznam členov z zamiki (privzeto)
+        Json,
+        /// JSON seznam členov brez presledkov
+        CompactJson,
+        /// En člen v vsaki vrstici, zapisan tako, da ga lahko natančno preberemo nazaj (tudi `NaN` in `inf`)
+        Csv,
+        /// En člen (JSON število) v vsaki vrstici
+        Ndjson,
+        /// Vsak člen je zapisan z 8 bajti kot `f64` v little-endian
+        Binary
+    }
+
+    /// Vsi zapisi, od najbolj do najmanj učinkovitega
+    pub const ENCODINGS: [Encoding; 5] = [Encoding::Binary, Encoding::Csv, Encoding::Ndjson, Encoding::CompactJson, Encoding::Json];
+
+    /// Media type iz headerja Accept ali Content-Type, npr. `application/json; compact=true; q=0.5`
+    struct MediaRange<'a> {
+        media_type: &'a str,
+        parameters: Vec<(&'a str, &'a str)>,
+        quality:    f32
+    }
+
+    fn media_ranges(header: &str) -> impl Iterator<Item = MediaRange<'_>> {
+        header.split(',').filter(|range| !range.trim().is_empty()).map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or("");
+            let parameters: Vec<(&str, &str)> = parts
+                .map(|parameter| parameter.split_once('=').map_or((parameter, ""), |(name, value)| (name.trim(), value.trim())))
+                .collect();
+            let quality = parameters.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("q"))
+                .map_or(1., |(_, q)| q.parse().unwrap_or(0.));
+            MediaRange { media_type, parameters, quality }
+        })
+    }
+
+    impl Encoding {
+        /// Vrednost headerja Content-Type za ta zapis
+        pub fn content_type(&self) -> &'static str {
+            match self {
+                Encoding::Json | Encoding::CompactJson  => "application/json",
+                Encoding::Csv                           => "text/csv",
+                Encoding::Ndjson                        => "application/x-ndjson",
+                Encoding::Binary                        => "application/octet-stream"
+            }
+        }
+
+        /// Oznaka zapisa v headerjih Accept in `X-Sequence-Encodings`
+        pub fn media_type(&self) -> &'static str {
+            match self {
+                Encoding::CompactJson   => "application/json; compact=true",
+                encoding                => encoding.content_type()
+            }
+        }
+
+        fn from_media_range(range: &MediaRange) -> Option<Self> {
+            let media_type = range.media_type.to_ascii_lowercase();
+            let compact = range.parameters.iter()
+                .any(|(name, value)| name.eq_ignore_ascii_case("compact") && !value.eq_ignore_ascii_case("false"));
+
+            match media_type.as_str() {
+                "application/json" if compact   => Some(Encoding::CompactJson),
+                "application/json" | "*/*" | "application/*" => Some(Encoding::Json),
+                "text/csv" | "text/*"           => Some(Encoding::Csv),
+                "application/x-ndjson"          => Some(Encoding::Ndjson),
+                "application/octet-stream"      => Some(Encoding::Binary),
+                _ => None
+            }
+        }
+
+        /// Izbere zapis glede na header Accept. Izbere podprt zapis z največjo utežjo `q`, 
+        /// med enakimi pa prvega. Če headerja ni, vrne JSON.
+        pub fn negotiate(accept: Option<&str>) -> Result<Self> {
+            Encoding::negotiate_from(accept, &ENCODINGS)
+        }
+
+        /// Kot `negotiate`, le da izbira le med podanimi zapisi (med katerimi mora biti JSON)
+        pub fn negotiate_from(accept: Option<&str>, supported: &[Encoding]) -> Result<Self> {
+            let accept = match accept {
+                Some(accept) if !accept.trim().is_empty() => accept,
+                _ => return Ok(Encoding::Json)
+            };
+
+            let mut best: Option<(Encoding, f32)> = None;
+            for range in media_ranges(accept).filter(|range| range.quality > 0.) {
+                if let Some(encoding) = Encoding::from_media_range(&range).filter(|encoding| supported.contains(encoding)) {
+                    if best.is_none_or(|(_, quality)| range.quality > quality) {
+                        best = Some((encoding, range.quality));
+                    }
+                }
+            }
+
+            match best {
+                Some((encoding, _)) => Ok(encoding),
+                None => Err(Error::not_acceptable(accept, &supported.iter().map(|encoding| encoding.media_type()).collect::<Vec<_>>()))
+            }
+        }
+
+        /// Izbere zapis glede na header Content-Type response-a (privzeto JSON)
+        pub fn from_content_type(content_type: Option<&str>) -> Self {
+            content_type
+                .and_then(|content_type| media_ranges(content_type).next())
+                .and_then(|range| Encoding::from_media_range(&range))
+                .unwrap_or(Encoding::Json)
+        }
+
+        /// Vrednost headerja `X-Sequence-Encodings`, s katero oglašujemo vse zapise
+        pub fn advertise() -> String {
+            ENCODINGS.map(|encoding| encoding.media_type()).join(", ")
+        }
+
+        /// Najbolj učinkovit zapis, ki ga oglašuje remote. Če remote ne oglašuje ničesar, vrne JSON.
+        pub fn preferred(advertised: Option<&str>) -> Self {
+            let advertised: Vec<Encoding> = advertised
+                .map(|advertised| media_ranges(advertised).filter_map(|range| Encoding::from_media_range(&range)).collect())
+                .unwrap_or_default();
+            ENCODINGS.into_iter().find(|encoding| advertised.contains(encoding)).unwrap_or(Encoding::Json)
+        }
+
+        /// Vrednost headerja Accept, s katero zahtevamo ta zapis, JSON pa dopuščamo kot rezervo
+        pub fn accept(&self) -> String {
+            match self {
+                Encoding::Json  => Encoding::Json.media_type().to_owned(),
+                encoding        => format!("{}, application/json; q=0.5", encoding.media_type())
+            }
+        }
+
+        /// Začetek telesa, preden zapišemo kakšen člen
+        pub fn start(&self) -> &'static [u8] {
+            match self {
+                Encoding::Json | Encoding::CompactJson  => b"[",
+                _ => b""
+            }
+        }
+
+        /// Konec telesa, ko smo zapisali vse člene
+        pub fn end(&self) -> &'static [u8] {
+            match self {
+                Encoding::Json | Encoding::CompactJson  => b"]",
+                _ => b""
+            }
+        }
+
+        /// Doda člen z indeksom `index` (šteto od 0) na konec `buffer`. Pri pošiljanju po kosih
+        /// JSON vedno zapišemo brez zamikov.
+        pub fn write_term(&self, index: u64, term: f64, buffer: &mut Vec<u8>) -> Result<()> {
+            match self {
+                Encoding::Json | Encoding::CompactJson => {
+                    if index > 0 { buffer.push(b','); }
+                    serde_json::to_writer(&mut *buffer, &term)?;
+                },
+                Encoding::Csv => {
+                    // Debug zapis f64 je najkrajši, ki se prebere nazaj v isto število
+                    buffer.extend_from_slice(format!("{term:?}\n").as_bytes());
+                },
+                Encoding::Ndjson => {
+                    serde_json::to_writer(&mut *buffer, &term)?;
+                    buffer.push(b'\n');
+                },
+                Encoding::Binary => buffer.extend_from_slice(&term.to_le_bytes())
+            }
+            Ok(())
+        }
+
+        /// Zapiše celotno zaporedje
+        pub fn encode(&self, terms: &[f64]) -> Result<Vec<u8>> {
+            if *self == Encoding::Json {
+                return Ok(serde_json::to_vec_pretty(terms)?);
+            }
+
+            let mut buffer = self.start().to_vec();
+            for (i, term) in terms.iter().enumerate() {
+                self.write_term(i as u64, *term, &mut buffer)?;
+            }
+            buffer.extend_from_slice(self.end());
+            Ok(buffer)
+        }
+
+        /// Prebere člene iz telesa response-a. JSON `null` preberemo kot `NaN`.
+        pub fn decode(&self, data: &[u8]) -> Result<Vec<f64>> {
+            let json_term = |term: Option<f64>| term.unwrap_or(f64::NAN);
+            match self {
+                Encoding::Json | Encoding::CompactJson => {
+                    let terms: Vec<Option<f64>> = serde_json::from_slice(data)?;
+                    Ok(terms.into_iter().map(json_term).collect())
+                },
+                Encoding::Csv => std::str::from_utf8(data)?
+                    .split(['\n', ','])
+                    .map(str::trim)
+                    .filter(|term| !term.is_empty())
+                    .map(|term| term.parse().map_err(|_| Error::malformed_request("Neveljaven člen v CSV")))
+                    .collect(),
+                Encoding::Ndjson => data.split(|&b| b == b'\n')
+                    .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+                    .map(|line| serde_json::from_slice(line).map(json_term))
+                    .collect::<serde_json::Result<Vec<f64>>>()
+                    .map_err(|_| Error::malformed_request("Neveljavna vrstica NDJSON")),
+                Encoding::Binary => {
+                    if !data.len().is_multiple_of(8) {
+                        return Err(Error::malformed_request("Dolžina binarnega zaporedja ni večkratnik 8"));
+                    }
+                    Ok(data.chunks_exact(8).map(|term| f64::from_le_bytes(term.try_into().unwrap())).collect())
+                }
+            }
+        }
+    }
+}
+
 pub mod parse_helper {
     use crate::error::Result;
 
@@ -208,4 +958,84 @@ pub mod parse_helper {
             Ok(serde_json::to_vec_pretty(&self)?)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encoding::{Encoding, ENCODINGS}, expression::parse_expression, sequence_provide::{parse_query, Range}};
+
+    #[test]
+    fn test_range_len() {
+        assert_eq!(Range { from: 0, to: 10, step: 1 }.len(), 10);
+        assert_eq!(Range { from: 4, to: 10, step: 3 }.len(), 2);
+        assert_eq!(Range { from: 13, to: 26, step: 2 }.len(), 7);
+        assert_eq!(Range { from: 5, to: 5, step: 1 }.len(), 0);
+        assert_eq!(Range { from: 0, to: u64::MAX, step: u64::MAX }.len(), 1);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Encoding::negotiate(None), Ok(Encoding::Json));
+        assert_eq!(Encoding::negotiate(Some("*/*")), Ok(Encoding::Json));
+        assert_eq!(Encoding::negotiate(Some("text/html, application/x-ndjson;q=0.9")), Ok(Encoding::Ndjson));
+        assert_eq!(Encoding::negotiate(Some("application/json;q=0.5, application/octet-stream")), Ok(Encoding::Binary));
+        assert_eq!(Encoding::negotiate(Some("text/csv;q=0.2, application/json; compact=true; q=0.8")), Ok(Encoding::CompactJson));
+        assert_eq!(Encoding::negotiate(Some("text/csv, application/octet-stream")), Ok(Encoding::Csv));
+        assert!(Encoding::negotiate(Some("text/html, application/json;q=0")).is_err());
+
+        assert_eq!(Encoding::from_content_type(Some("application/octet-stream")), Encoding::Binary);
+        assert_eq!(Encoding::from_content_type(Some("text/html")), Encoding::Json);
+        assert_eq!(Encoding::preferred(Some(&Encoding::advertise())), Encoding::Binary);
+        assert_eq!(Encoding::preferred(Some("application/x-ndjson, text/csv")), Encoding::Csv);
+        assert_eq!(Encoding::preferred(None), Encoding::Json);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let terms = [1.5, -2., 1e300, f64::INFINITY];
+        for encoding in ENCODINGS {
+            let data = encoding.encode(&terms).unwrap();
+            let decoded = encoding.decode(&data);
+            // JSON ne pozna neskončnosti
+            match encoding {
+                Encoding::Csv | Encoding::Binary => assert_eq!(decoded, Ok(terms.to_vec())),
+                _ => assert_eq!(decoded.unwrap()[..3], terms[..3])
+            }
+        }
+
+        assert_eq!(Encoding::CompactJson.encode(&[1., 2.]), Ok(b"[1.0,2.0]".to_vec()));
+        assert_eq!(Encoding::Csv.decode(b"1.0\nNaN\n").unwrap()[0], 1.);
+        assert!(Encoding::Csv.decode(b"1.0\nNaN\n").unwrap()[1].is_nan());
+        assert!(Encoding::Ndjson.decode(b"1\n[2]\n").is_err());
+        assert!(Encoding::Binary.decode(&[0; 9]).is_err());
+    }
+
+    #[test]
+    fn test_expression() {
+        let sequence = parse_expression("sum(arithmetic(0,2), drop[3](geometric(1,2)))").unwrap();
+        assert_eq!(sequence.name, "sum");
+        assert_eq!((sequence.sequences[0].name.as_str(), sequence.sequences[0].parameters.as_slice()), ("arithmetic", [0., 2.].as_slice()));
+        assert_eq!((sequence.sequences[1].parameters.as_slice(), sequence.sequences[1].sequences[0].name.as_str()), ([3.].as_slice(), "geometric"));
+        assert_eq!(sequence.to_string(), "sum(arithmetic(0, 2), drop[3](geometric(1, 2)))");
+
+        for expression in ["p_euler", "lin_com[2, -0.5](const(1000), arithmetic(0, 1))", "round[2](sum(const(1.25), const(-3)))"] {
+            assert_eq!(parse_expression(expression).unwrap().to_string(), expression);
+        }
+        assert_eq!(parse_expression(" lin_com ( 2, 3, const(1), p_euler() ) ").unwrap().to_string(), "lin_com[2, 3](const(1), p_euler)");
+
+        let (name, request) = parse_query(br#"{"expression": "drop[1](const(2))", "range": {"from": 0, "to": 3, "step": 1}}"#).unwrap();
+        assert_eq!((name.as_str(), request.parameters, request.sequences[0].name.as_str()), ("drop", vec![1.], "const"));
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let column = |expression: &str| serde_json::to_value(parse_expression(expression).unwrap_err()).unwrap()["extra"]["column"].as_u64();
+
+        assert_eq!(column("sum(arithmetic(0,2) drop(1))"), Some(21));
+        assert_eq!(column("drop[3](1)"), Some(9));
+        assert_eq!(column("sum(const(1x))"), Some(11));
+        assert_eq!(column("const(1))"), Some(9));
+        assert_eq!(column("(1)"), Some(1));
+        assert_eq!(column("sum(const(1), "), Some(15));
+    }
+}
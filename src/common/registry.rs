@@ -0,0 +1,268 @@
+//! Register generatorjev centralnega strežnika, ki ga hranimo v datoteki, da ob ponovnem zagonu
+//! ne pozabimo celotnega omrežja
+
+use std::{collections::HashMap, io::ErrorKind, path::{Path, PathBuf}, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+use crate::{error::Result, parse::{remote::Remote, sequence_provide::{CatalogEntry, CatalogProvider, SequenceInfo}}};
+
+/// Zaporedja, ki jih ponuja generator, in zapisi, v katerih jih lahko pošlje
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    pub sequences:  Vec<SequenceInfo>,
+    pub encodings:  Option<String>
+}
+
+/// Rezultat pinga generatorja in, če je bil uspešen, njegov katalog (če smo ga uspeli pridobiti)
+pub struct Ping {
+    pub remote:     Remote,
+    pub success:    bool,
+    pub catalog:    Option<Catalog>
+}
+
+/// Registriran generator, kot ga vrnemo na `GET /project/` in zapišemo v datoteko
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RegistryEntry {
+    #[serde(flatten)]
+    pub remote:         Remote,
+    /// Čas (v sekundah od 1. 1. 1970), ko se je generator nazadnje registriral ali odzval na ping
+    #[serde(default)]
+    pub last_seen:      u64,
+    /// Število zaporednih zgrešenih pingov
+    #[serde(default)]
+    pub missed_pings:   u32,
+    /// Kataloga ne shranjujemo, saj ga ob zagonu takoj ponovno pridobimo
+    #[serde(skip)]
+    pub catalog:        Catalog
+}
+
+pub struct Registry {
+    entries:    HashMap<Remote, RegistryEntry>,
+    path:       PathBuf,
+    /// Zaporedna številka zadnje spremembe registra
+    generation: u64,
+    /// Zaporedna številka zadnjega stanja, zapisanega v datoteko
+    written:    Arc<Mutex<u64>>
+}
+
+/// Serializirano stanje registra, ki ga zapišemo v datoteko šele, ko sprostimo zaklep registra
+#[must_use]
+pub struct Snapshot {
+    data:       Vec<u8>,
+    generation: u64,
+    path:       PathBuf,
+    written:    Arc<Mutex<u64>>
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+impl Registry {
+    /// Prebere register iz datoteke. Če ta še ne obstaja, je register prazen.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let entries = match fs::read(path).await {
+            Ok(data) => serde_json::from_slice::<Vec<RegistryEntry>>(&data)?.into_iter()
+                .map(|entry| (entry.remote.clone(), entry))
+                .collect(),
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => Err(err)?
+        };
+
+        Ok(Registry { entries, path: path.to_owned(), generation: 0, written: Arc::new(Mutex::new(0)) })
+    }
+
+    pub fn remotes(&self) -> impl Iterator<Item = &Remote> {
+        self.entries.keys()
+    }
+
+    /// Vnosi registra, urejeni po naslovu
+    pub fn entries(&self) -> Vec<&RegistryEntry> {
+        let mut entries: Vec<&RegistryEntry> = self.entries.values().collect();
+        entries.sort_by_key(|entry| entry.remote.get_url());
+        entries
+    }
+
+    /// Doda (ali osveži) generator in vrne novo stanje za zapis v datoteko. Če katalog ni podan, obdržimo prejšnjega.
+    pub fn insert(&mut self, remote: Remote, catalog: Option<Catalog>) -> Result<Snapshot> {
+        let catalog = catalog.or_else(|| self.entries.get(&remote).map(|entry| entry.catalog.clone())).unwrap_or_default();
+        let entry = RegistryEntry { remote: remote.clone(), last_seen: now(), missed_pings: 0, catalog };
+        self.entries.insert(remote, entry);
+        self.snapshot()
+    }
+
+    /// Odstrani podane generatorje in, če se je register spremenil, vrne novo stanje za zapis v datoteko
+    pub fn remove(&mut self, remotes: &[Remote]) -> Result<Option<Snapshot>> {
+        let length = self.entries.len();
+        self.entries.retain(|remote, _| !remotes.contains(remote));
+        if self.entries.len() != length { self.snapshot().map(Some) } else { Ok(None) }
+    }
+
+    /// Zapiše rezultate pingov. Generatorje, ki so zgrešili `grace` zaporednih pingov, odstrani in jih vrne
+    /// skupaj z novim stanjem za zapis v datoteko.
+    pub fn record_pings(&mut self, pings: Vec<Ping>, grace: u32) -> Result<(Vec<Remote>, Snapshot)> {
+        let mut evicted = vec![];
+        for Ping { remote, success, catalog } in pings {
+            // generator se je morda medtem odjavil
+            let Some(entry) = self.entries.get_mut(&remote) else { continue };
+            if success {
+                entry.last_seen = now();
+                entry.missed_pings = 0;
+                if let Some(catalog) = catalog { entry.catalog = catalog; }
+            } else {
+                entry.missed_pings += 1;
+                if entry.missed_pings >= grace { evicted.push(remote.clone()); }
+            }
+        }
+
+        self.entries.retain(|remote, _| !evicted.contains(remote));
+        Ok((evicted, self.snapshot()?))
+    }
+
+    /// Zaporedja iz katalogov vseh generatorjev, ki ustrezajo podanim pogojem, vsako z vsemi generatorji, ki ga ponujajo
+    pub fn search(&self, name: Option<&str>, parameters: Option<usize>, sequences: Option<usize>) -> Vec<CatalogEntry> {
+        let mut result: Vec<CatalogEntry> = vec![];
+        for entry in self.entries() {
+            let matching = entry.catalog.sequences.iter().filter(|info| {
+                name.is_none_or(|name| info.name == name)
+                    && parameters.is_none_or(|parameters| info.parameters == parameters)
+                    && sequences.is_none_or(|sequences| info.sequences == sequences)
+            });
+
+            for info in matching {
+                let provider = CatalogProvider { remote: entry.remote.clone(), encodings: entry.catalog.encodings.clone() };
+                match result.iter_mut().find(|found| &found.info == info) {
+                    Some(found) => found.providers.push(provider),
+                    None => result.push(CatalogEntry { info: info.clone(), providers: vec![provider] })
+                }
+            }
+        }
+        result
+    }
+
+    // register serializiramo, dokler ga imamo zaklenjenega, zapišemo pa ga šele po sprostitvi zaklepa
+    fn snapshot(&mut self) -> Result<Snapshot> {
+        self.generation += 1;
+        Ok(Snapshot {
+            data:       serde_json::to_vec_pretty(&self.entries())?,
+            generation: self.generation,
+            path:       self.path.clone(),
+            written:    self.written.clone()
+        })
+    }
+}
+
+impl Snapshot {
+    /// Zapiše stanje v datoteko, razen če je bilo medtem zapisano že novejše stanje
+    pub async fn save(self) -> Result<()> {
+        // hkratni zapisi se čakajo, da starejše stanje ne prepiše novejšega
+        let mut written = self.written.lock().await;
+        if *written >= self.generation { return Ok(()); }
+
+        // register najprej zapišemo v začasno datoteko in jo nato preimenujemo, tako da ob sesutju
+        // datoteka vedno vsebuje bodisi stari bodisi novi register
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        let mut file = fs::File::create(&temporary).await?;
+        file.write_all(&self.data).await?;
+        file.sync_all().await?;
+        fs::rename(&temporary, &self.path).await?;
+        *written = self.generation;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::parse::{remote::Remote, sequence_provide::SequenceInfo};
+    use super::{Catalog, Ping, Registry};
+
+    #[tokio::test]
+    async fn test_persist() {
+        let path = std::env::temp_dir().join(format!("float-torrent-register-{}.json", std::process::id()));
+        let remote = |port| Remote::new("generator", "127.0.0.1", port).unwrap();
+
+        let mut registry = Registry::load(&path).await.unwrap();
+        assert_eq!(registry.remotes().count(), 0);
+        registry.insert(remote(1), None).unwrap().save().await.unwrap();
+        registry.insert(remote(2), None).unwrap().save().await.unwrap();
+
+        let mut registry = Registry::load(&path).await.unwrap();
+        assert_eq!(registry.remotes().cloned().collect::<HashSet<_>>(), HashSet::from([remote(1), remote(2)]));
+        registry.remove(&[remote(1), remote(3)]).unwrap().unwrap().save().await.unwrap();
+        assert!(registry.remove(&[remote(3)]).unwrap().is_none());
+        assert_eq!(Registry::load(&path).await.unwrap().remotes().collect::<Vec<_>>(), vec![&remote(2)]);
+
+        // register v starejši obliki, brez časa zadnjega odziva
+        std::fs::write(&path, r#"[{"name": "generator", "ip": "127.0.0.1", "port": 3}]"#).unwrap();
+        assert_eq!(Registry::load(&path).await.unwrap().entries()[0].last_seen, 0);
+
+        std::fs::write(&path, "[{").unwrap();
+        assert!(Registry::load(&path).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_record_pings() {
+        let path = std::env::temp_dir().join(format!("float-torrent-register-pingi-{}.json", std::process::id()));
+        let remote = |port| Remote::new("generator", "127.0.0.1", port).unwrap();
+
+        let mut registry = Registry::load(&path).await.unwrap();
+        registry.insert(remote(1), None).unwrap().save().await.unwrap();
+        registry.insert(remote(2), None).unwrap().save().await.unwrap();
+
+        let pings = || [(1, true), (2, false), (3, false)].map(|(port, success)| Ping { remote: remote(port), success, catalog: None }).into();
+        let (evicted, snapshot) = registry.record_pings(pings(), 2).unwrap();
+        assert!(evicted.is_empty());
+        snapshot.save().await.unwrap();
+        assert_eq!(registry.entries().iter().map(|entry| entry.missed_pings).collect::<Vec<_>>(), vec![0, 1]);
+        let (evicted, snapshot) = registry.record_pings(pings(), 2).unwrap();
+        assert_eq!(evicted, vec![remote(2)]);
+        snapshot.save().await.unwrap();
+
+        let registry = Registry::load(&path).await.unwrap();
+        assert_eq!(registry.remotes().collect::<Vec<_>>(), vec![&remote(1)]);
+        assert!(registry.entries()[0].last_seen > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_order() {
+        let path = std::env::temp_dir().join(format!("float-torrent-register-vrstni-red-{}.json", std::process::id()));
+        let remote = |port| Remote::new("generator", "127.0.0.1", port).unwrap();
+
+        // starejše stanje, zapisano za novejšim, ne sme prepisati novejšega
+        let mut registry = Registry::load(&path).await.unwrap();
+        let older = registry.insert(remote(1), None).unwrap();
+        let newer = registry.insert(remote(2), None).unwrap();
+        newer.save().await.unwrap();
+        older.save().await.unwrap();
+        assert_eq!(Registry::load(&path).await.unwrap().remotes().count(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let path = std::env::temp_dir().join(format!("float-torrent-register-katalog-{}.json", std::process::id()));
+        let remote = |port| Remote::new("generator", "127.0.0.1", port).unwrap();
+        let info = |name: &str, parameters| SequenceInfo { name: name.to_owned(), description: "".to_owned(), parameters, sequences: 0 };
+        let catalog = |sequences| Some(Catalog { sequences, encodings: None });
+
+        let mut registry = Registry::load(&path).await.unwrap();
+        registry.insert(remote(1), catalog(vec![info("linear_rec_h", 4), info("linear_rec_h", 6)])).unwrap().save().await.unwrap();
+        registry.insert(remote(2), catalog(vec![info("linear_rec_h", 6), info("const", 1)])).unwrap().save().await.unwrap();
+        registry.insert(remote(2), None).unwrap().save().await.unwrap();
+
+        let found = registry.search(Some("linear_rec_h"), Some(6), None);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].providers.iter().map(|provider| provider.remote.port).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(registry.search(Some("linear_rec_h"), None, None).len(), 2);
+        assert_eq!(registry.search(None, None, None).len(), 3);
+        assert!(registry.search(Some("const"), Some(0), None).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::parse::sequence_provide::{self, SequenceParameter};
+
+/// Statistika cache-a, ki jo vrnemo na endpointu `/cache`
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CacheStats {
+    pub capacity:   usize,
+    pub ttl:        u64,
+    pub entries:    usize,
+    pub hits:       u64,
+    pub misses:     u64
+}
+
+struct Entry {
+    inserted:   Instant,
+    values:     Vec<f64>
+}
+
+/// Omejen cache izračunanih zaporedij, kjer je ključ kanonična oblika drevesa zahteve.
+/// Ko je cache poln, zavržemo najstarejši vnos, vnosi pa zastarajo po `ttl`.
+pub struct SequenceCache {
+    capacity:   usize,
+    ttl:        Duration,
+    entries:    HashMap<String, Entry>,
+    order:      VecDeque<(String, Instant)>,
+    hits:       u64,
+    misses:     u64
+}
+
+// parameter zapišemo tako, da sta 0 in -0 enaka
+fn write_number(key: &mut String, value: f64) {
+    let value = if value == 0. { 0. } else { value };
+    key.push_str(&value.to_string());
+}
+
+fn write_sequence(key: &mut String, name: &str, parameters: &[f64], sequences: &[SequenceParameter]) {
+    key.push_str(name);
+    key.push('[');
+    for (i, p) in parameters.iter().enumerate() {
+        if i > 0 { key.push(','); }
+        write_number(key, *p);
+    }
+    key.push_str("](");
+    for (i, s) in sequences.iter().enumerate() {
+        if i > 0 { key.push(','); }
+        write_sequence(key, &s.name, &s.parameters, &s.sequences);
+    }
+    key.push(')');
+}
+
+/// Vrne kanoničen ključ zahteve za zaporedje z imenom `name`
+pub fn key(name: &str, request: &sequence_provide::Request) -> String {
+    let mut key = String::new();
+    write_sequence(&mut key, name, &request.parameters, &request.sequences);
+    let range = request.range;
+    key.push_str(&format!("@{}:{}:{}", range.from, range.to, range.step));
+    key
+}
+
+impl SequenceCache {
+    /// Ustvari cache, ki hrani največ `capacity` rezultatov (0 ga izklopi)
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        SequenceCache {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Vec<f64>> {
+        let fresh = match self.entries.get(key) {
+            None => None,
+            Some(entry) => Some(entry.inserted.elapsed() < self.ttl)
+        };
+
+        match fresh {
+            Some(true) => {
+                self.hits += 1;
+                self.entries.get(key).map(|entry| entry.values.clone())
+            },
+            Some(false) => {
+                self.entries.remove(key);
+                self.misses += 1;
+                None
+            },
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: String, values: Vec<f64>) {
+        if self.capacity == 0 { return; }
+
+        // odstranimo najstarejše vnose, dokler ni prostora
+        while !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            match self.order.pop_front() {
+                None => break,
+                Some((old, inserted)) => {
+                    if self.entries.get(&old).is_some_and(|entry| entry.inserted == inserted) {
+                        self.entries.remove(&old);
+                    }
+                }
+            }
+        }
+
+        let inserted = Instant::now();
+        self.order.push_back((key.clone(), inserted));
+        self.entries.insert(key, Entry { inserted, values });
+
+        // ponovno vstavljeni in zastareli ključi v `order` pustijo zastarele zapise, ki jih občasno počistimo,
+        // tako da ima `order` največ 2 * capacity zapisov
+        if self.order.len() > 2 * self.capacity {
+            let entries = &self.entries;
+            self.order.retain(|(key, inserted)| entries.get(key).is_some_and(|entry| entry.inserted == *inserted));
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            capacity: self.capacity,
+            ttl: self.ttl.as_secs(),
+            entries: self.entries.len(),
+            hits: self.hits,
+            misses: self.misses
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::parse::sequence_provide::{Domain, Range, Request, SequenceParameter};
+    use super::{key, SequenceCache};
+
+    fn request(parameter: f64) -> Request {
+        Request {
+            range: Range { from: 0, to: 5, step: 1 },
+            parameters: vec![],
+            sequences: vec![
+                SequenceParameter { name: "const".to_owned(), parameters: vec![parameter], sequences: vec![] },
+                SequenceParameter { name: "arithmetic".to_owned(), parameters: vec![1., 2.], sequences: vec![] }
+            ],
+            visited: vec![],
+            deadline_ms: None,
+            deadline: None,
+            domain: Domain::Float
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        assert_eq!(key("sum", &request(1.5)), "sum[](const[1.5](),arithmetic[1,2]())@0:5:1");
+        assert_eq!(key("sum", &request(0.)), key("sum", &request(-0.)));
+        assert_ne!(key("sum", &request(1.)), key("prod", &request(1.)));
+    }
+
+    #[test]
+    fn test_hits_and_eviction() {
+        let mut cache = SequenceCache::new(2, Duration::from_secs(60));
+        assert_eq!(cache.get("a"), None);
+
+        cache.insert("a".to_owned(), vec![1.]);
+        cache.insert("b".to_owned(), vec![2.]);
+        assert_eq!(cache.get("a"), Some(vec![1.]));
+
+        cache.insert("c".to_owned(), vec![3.]);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("c"), Some(vec![3.]));
+
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 2, 2));
+    }
+
+    #[test]
+    fn test_reinsert_bounded() {
+        let mut cache = SequenceCache::new(2, Duration::from_secs(60));
+        for i in 0..1000 {
+            cache.insert("a".to_owned(), vec![i as f64]);
+            cache.insert("b".to_owned(), vec![i as f64]);
+            assert!(cache.order.len() <= 4);
+        }
+        assert_eq!(cache.get("a"), Some(vec![999.]));
+
+        // zastareli zapisi ne izrinejo svežih vnosov
+        cache.insert("c".to_owned(), vec![3.]);
+        assert_eq!((cache.get("a"), cache.get("b")), (None, Some(vec![999.])));
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let mut cache = SequenceCache::new(2, Duration::ZERO);
+        cache.insert("a".to_owned(), vec![1.]);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.stats().entries, 0);
+
+        let mut cache = SequenceCache::new(0, Duration::from_secs(60));
+        cache.insert("a".to_owned(), vec![1.]);
+        assert_eq!(cache.get("a"), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Točne domene zaporedij. Cela in racionalna števila predstavimo z `BigRational` (cela imajo imenovalec 1),
+//! v JSON pa jih pošiljamo kot nize, npr. `"12345678901234567890"` ali `"-3/4"`, da ne izgubimo števk.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{error::{Error, Result}, parse::sequence_provide::{Domain, Term}};
+
+/// Točen člen zaporedja
+pub type Exact = BigRational;
+
+/// Pretvori parameter iz JSON števila v točno število. V domeni Integer mora biti parameter celo število,
+/// točna pa so le cela števila do 2^53 in dvojiški ulomki, saj parametre še vedno pošiljamo kot `f64`.
+pub fn from_f64(value: f64, domain: Domain) -> Result<Exact> {
+    let exact = BigRational::from_float(value).ok_or(Error::invalid_exact_value(&value.to_string(), domain))?;
+    check(exact, domain).map_err(|_| Error::invalid_exact_value(&value.to_string(), domain))
+}
+
+/// Preveri, da je število v domeni
+pub fn check(value: Exact, domain: Domain) -> Result<Exact> {
+    if domain == Domain::Integer && !value.is_integer() {
+        Err(Error::invalid_exact_value(&value.to_string(), domain))
+    } else { Ok(value) }
+}
+
+pub fn from_integer(value: impl Into<BigInt>) -> Exact {
+    BigRational::from_integer(value.into())
+}
+
+pub fn zero() -> Exact {
+    BigRational::zero()
+}
+
+/// Člene zapiše kot nize
+pub fn to_strings(terms: &[Exact]) -> Vec<String> {
+    terms.iter().map(Exact::to_string).collect()
+}
+
+/// Prebere člene, zapisane kot nizi, in preveri, da so v domeni
+pub fn from_strings(terms: &[String], domain: Domain) -> Result<Vec<Exact>> {
+    terms.iter().map(|term| {
+        let value: Exact = term.trim().parse().map_err(|_| Error::invalid_exact_value(term, domain))?;
+        check(value, domain)
+    }).collect()
+}
+
+/// Prebere člen, podan kot JSON število ali niz
+pub fn from_term(term: &Term, domain: Domain) -> Result<Exact> {
+    match term {
+        Term::Number(value) => from_f64(*value, domain),
+        Term::Exact(value) => Ok(from_strings(std::slice::from_ref(value), domain)?.remove(0))
+    }
+}
+
+/// Pretvori točno število v `f64` brez zaokrožanja in vrne napako, če to ni mogoče
+pub fn to_f64_exact(value: &Exact) -> Result<f64> {
+    to_f64(value).ok().filter(|float| BigRational::from_float(*float).as_ref() == Some(value)).ok_or(Error::inexact_f64(&value.to_string()))
+}
+
+/// Približno število bitov točne vrednosti parametra (števca in imenovalca skupaj), s katerim ocenimo velikost členov
+pub fn bits(value: f64) -> f64 {
+    BigRational::from_float(value).map_or(0., |value| log2(value.numer()) + log2(value.denom()))
+}
+
+/// Približno število bitov imenovalca točne vrednosti parametra
+pub fn denominator_bits(value: f64) -> f64 {
+    BigRational::from_float(value).map_or(0., |value| log2(value.denom()))
+}
+
+/// Število bitov točnega števila (števca in imenovalca skupaj)
+pub fn size_bits(value: &Exact) -> u64 {
+    value.numer().bits() + value.denom().bits()
+}
+
+fn log2(value: &BigInt) -> f64 {
+    value.to_f64().unwrap_or(f64::MAX).abs().max(1.).log2()
+}
+
+/// Pretvori točno število nazaj v `f64`, npr. za parametre zaporedij
+pub fn to_f64(value: &Exact) -> Result<f64> {
+    value.to_f64().filter(|value| value.is_finite()).ok_or(Error::invalid_exact_value(&value.to_string(), Domain::Float))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::sequence_provide::Domain;
+    use super::{from_f64, from_strings, to_strings};
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(to_strings(&[from_f64(-3., Domain::Integer).unwrap(), from_f64(0.75, Domain::Rational).unwrap()]), vec!["-3", "3/4"]);
+        assert!(from_f64(0.5, Domain::Integer).is_err());
+        assert!(from_f64(f64::NAN, Domain::Rational).is_err());
+
+        let big = "354224848179261915075".to_owned();
+        assert_eq!(to_strings(&from_strings(&[big.clone(), "6/8".to_owned()], Domain::Rational).unwrap()), vec![big.clone(), "3/4".to_owned()]);
+        assert!(from_strings(&["6/8".to_owned()], Domain::Integer).is_err());
+        assert!(from_strings(&["1.5".to_owned()], Domain::Rational).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::parse::remote::Remote;
+
+/// Utež novega vzorca pri eksponentnem drsečem povprečju (EWMA)
+const ALPHA: f64 = 0.3;
+
+/// Stanje nekega oddaljenega generatorja, kot ga vidimo mi
+#[derive(Clone)]
+struct RemoteHealth {
+    success_rate:           f64,
+    latency_ms:             f64,
+    successes:              u64,
+    failures:               u64,
+    consecutive_failures:   u32,
+    ejected_until:          Option<Instant>
+}
+
+impl Default for RemoteHealth {
+    // o generatorjih, ki jih še nismo poskusili, smo optimistični
+    fn default() -> Self {
+        RemoteHealth {
+            success_rate: 1.,
+            latency_ms: 0.,
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            ejected_until: None
+        }
+    }
+}
+
+impl RemoteHealth {
+    fn is_ejected(&self) -> bool {
+        self.ejected_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Višja ocena pomeni bolj zanesljiv in hitrejši generator
+    fn score(&self) -> f64 {
+        self.success_rate / (1. + self.latency_ms / 100.)
+    }
+}
+
+/// Poročilo o zdravju generatorja, ki ga vrnemo na endpointu `/providers`
+#[derive(Serialize, Debug)]
+pub struct HealthReport {
+    pub remote:                 Remote,
+    pub healthy:                bool,
+    pub score:                  f64,
+    pub success_rate:           f64,
+    pub latency_ms:             f64,
+    pub successes:              u64,
+    pub failures:               u64,
+    pub consecutive_failures:   u32,
+    pub ejected_for:            u64
+}
+
+/// Hrani uspešnost in odzivnost oddaljenih generatorjev. Generator, ki zaporedoma
+/// `failure_threshold`-krat ne uspe, za čas `ejection` izločimo (circuit breaker), nato pa mu
+/// prepustimo eno poskusno zahtevo.
+pub struct HealthTracker {
+    remotes:            HashMap<Remote, RemoteHealth>,
+    failure_threshold:  u32,
+    ejection:           Duration
+}
+
+impl HealthTracker {
+    pub fn new(failure_threshold: u32, ejection: Duration) -> Self {
+        HealthTracker { remotes: HashMap::new(), failure_threshold: failure_threshold.max(1), ejection }
+    }
+
+    pub fn record_success(&mut self, remote: &Remote, latency: Duration) {
+        let health = self.remotes.entry(remote.clone()).or_default();
+        let latency_ms = latency.as_secs_f64() * 1000.;
+
+        health.latency_ms = if health.successes == 0 { latency_ms } else { ALPHA * latency_ms + (1. - ALPHA) * health.latency_ms };
+        health.success_rate = ALPHA + (1. - ALPHA) * health.success_rate;
+        health.successes += 1;
+        health.consecutive_failures = 0;
+        health.ejected_until = None;
+    }
+
+    pub fn record_failure(&mut self, remote: &Remote) {
+        let health = self.remotes.entry(remote.clone()).or_default();
+
+        health.success_rate *= 1. - ALPHA;
+        health.failures += 1;
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.failure_threshold {
+            health.ejected_until = Some(Instant::now() + self.ejection);
+        }
+    }
+
+    pub fn is_healthy(&self, remote: &Remote) -> bool {
+        !self.remotes.get(remote).is_some_and(|health| health.is_ejected())
+    }
+
+    pub fn score(&self, remote: &Remote) -> f64 {
+        self.remotes.get(remote).cloned().unwrap_or_default().score()
+    }
+
+    /// Izpusti izločene generatorje in ostale uredi tako, da so zanesljivi in hitri spredaj. Generatorje, ki jim je
+    /// izločitev potekla, obdrži, a jim pred poskusom `admit` prepusti le eno zahtevo.
+    /// Urejanje je stabilno, tako da enako ocenjeni ohranijo prejšnji (naključni) vrstni red.
+    pub fn select<T>(&self, candidates: Vec<T>, remote: impl Fn(&T) -> &Remote) -> Vec<T> {
+        let mut selected: Vec<T> = candidates.into_iter().filter(|candidate| self.is_healthy(remote(candidate))).collect();
+        selected.sort_by(|a, b| self.score(remote(b)).total_cmp(&self.score(remote(a))));
+        selected
+    }
+
+    /// Pove, ali generatorju lahko pošljemo zahtevo. Kličemo ga tik pred poskusom, da poskusne zahteve ne porabimo za
+    /// generator, ki ga nato sploh ne poskusimo. Ko čas izločitve poteče, generator prepustimo le eni zahtevi (half-open),
+    /// za ostale pa ga izločimo še za en čas izločitve. Če poskus uspe, ga `record_success` vrne med zdrave, sicer ga
+    /// `record_failure` znova izloči.
+    pub fn admit(&mut self, remote: &Remote) -> bool {
+        let now = Instant::now();
+        let Some(health) = self.remotes.get_mut(remote) else { return true };
+        match health.ejected_until {
+            Some(until) if now < until => false,
+            Some(_) => {
+                health.ejected_until = Some(now + self.ejection);
+                true
+            },
+            None => true
+        }
+    }
+
+    pub fn report(&self, remote: &Remote) -> HealthReport {
+        let health = self.remotes.get(remote).cloned().unwrap_or_default();
+        HealthReport {
+            remote: remote.clone(),
+            healthy: !health.is_ejected(),
+            score: health.score(),
+            success_rate: health.success_rate,
+            latency_ms: health.latency_ms,
+            successes: health.successes,
+            failures: health.failures,
+            consecutive_failures: health.consecutive_failures,
+            ejected_for: health.ejected_until.map(|until| until.saturating_duration_since(Instant::now()).as_secs()).unwrap_or(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::parse::remote::Remote;
+    use super::HealthTracker;
+
+    #[test]
+    fn test_select() {
+        let (fast, slow, new) = (
+            Remote::new("fast", "127.0.0.1", 1).unwrap(),
+            Remote::new("slow", "127.0.0.1", 2).unwrap(),
+            Remote::new("new", "127.0.0.1", 3).unwrap()
+        );
+
+        let mut tracker = HealthTracker::new(2, Duration::from_secs(60));
+        tracker.record_success(&fast, Duration::from_millis(10));
+        tracker.record_success(&slow, Duration::from_millis(500));
+
+        let candidates = vec![slow.clone(), fast.clone(), new.clone()];
+        assert_eq!(tracker.select(candidates.clone(), |remote| remote), vec![new.clone(), fast.clone(), slow.clone()]);
+
+        tracker.record_failure(&new);
+        tracker.record_failure(&new);
+        assert!(!tracker.is_healthy(&new));
+
+        // izločenega generatorja ne poskusimo
+        assert_eq!(tracker.select(candidates.clone(), |remote| remote), vec![fast.clone(), slow.clone()]);
+
+        let report = tracker.report(&new);
+        assert_eq!((report.healthy, report.failures, report.consecutive_failures), (false, 2, 2));
+        assert!(report.ejected_for > 0);
+
+        tracker.record_success(&new, Duration::from_millis(1));
+        assert!(tracker.is_healthy(&new));
+    }
+
+    #[test]
+    fn test_half_open() {
+        let (dead, alive) = (Remote::new("dead", "127.0.0.1", 1).unwrap(), Remote::new("alive", "127.0.0.1", 2).unwrap());
+        let mut tracker = HealthTracker::new(1, Duration::from_millis(50));
+        tracker.record_failure(&dead);
+        assert_eq!(tracker.select(vec![dead.clone(), alive.clone()], |remote| remote), vec![alive.clone()]);
+
+        // po izteku izločitve generator spet izberemo, a mu prepustimo le eno poskusno zahtevo
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(tracker.select(vec![dead.clone(), alive.clone()], |remote| remote), vec![alive.clone(), dead.clone()]);
+        assert_eq!(tracker.select(vec![dead.clone(), alive.clone()], |remote| remote), vec![alive.clone(), dead.clone()]);
+        assert!(tracker.admit(&dead));
+        assert!(!tracker.admit(&dead));
+        assert!(tracker.admit(&alive));
+        assert_eq!(tracker.select(vec![dead.clone(), alive.clone()], |remote| remote), vec![alive.clone()]);
+
+        tracker.record_success(&dead, Duration::from_millis(1));
+        assert_eq!(tracker.select(vec![dead.clone(), alive.clone()], |remote| remote).len(), 2);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     error::{Error, Result}, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::FunctionSequence
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::{self, Exact}, FunctionSequence}
 };
 
 pub struct Sequence {}
@@ -23,6 +23,14 @@ impl FunctionSequence for Sequence {
             Ok(parameters[0] + parameters[1] * (n as f64))
         }
     }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn evaluate_exact(&self, parameters: &[Exact], n: u64) -> Result<Exact> {
+        Ok(&parameters[0] + &parameters[1] * exact::from_integer(n))
+    }
 }
 
 #[test]
@@ -1,7 +1,9 @@
+use std::{iter, sync::Arc};
+
 use crate::{
-    parse::sequence_provide::{self, SequenceInfo}, 
+    parse::sequence_provide::{self, Domain, SequenceInfo}, 
     error::Result,
-    sequnce_providers::SequenceProvider
+    sequnce_providers::{exact::Exact, SequenceProvider, Terms}
 };
 
 pub struct Provider {}
@@ -17,13 +19,19 @@ impl SequenceProvider for Provider {
     }
 
     fn generate(&self, range: sequence_provide::Range, parameters: &[f64], _sequences: &[Vec<f64>]) -> Result<Vec<f64>> {
-        let mut result = vec![];
-        
-        let mut i = range.from; 
-        while i < range.to {
-            result.push(parameters[0]); i += range.step;
-        }
-        Ok(result) 
+        Ok(vec![parameters[0]; range.len().try_into()?])
+    }
+
+    fn generate_iter(self: Arc<Self>, range: sequence_provide::Range, parameters: Vec<f64>, _sequences: Vec<Vec<f64>>) -> Result<Terms> {
+        Ok(Box::new(iter::repeat_n(parameters[0], range.len().try_into()?).map(Ok)))
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _sequences: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        Ok(vec![parameters[0].clone(); range.len().try_into()?])
     }
 }
 
@@ -1,10 +1,12 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 
 use crate::{
     error::{Error, Result}, 
-    parse::sequence_provide::{self, SequenceInfo}, 
-    sequnce_providers::{ProviderManager, SequenceProvider}
+    parse::sequence_provide::{self, Domain, SequenceInfo}, 
+    sequnce_providers::{exact::Exact, ProviderManager, SequenceProvider, Terms}
 };
 
 pub struct Provider {}
@@ -24,20 +26,40 @@ impl SequenceProvider for Provider {
         panic!("Unreachable code!")
     }
 
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
     // modificiramo Range v requestu in prepošljemo naprej
-    async fn provide(&self, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
+    async fn provide(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
+        let (name, ammended) = self.ammend(&request)?;
+        ProviderManager::provide(&name, ammended, manager).await
+    }
+
+    async fn provide_iter(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Terms> {
+        let (name, ammended) = self.ammend(&request)?;
+        ProviderManager::provide_terms(&name, ammended, manager).await
+    }
+
+    async fn provide_exact(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<Exact>> {
+        let (name, ammended) = self.ammend(&request)?;
+        ProviderManager::provide_exact(&name, ammended, manager).await
+    }
+}
+
+impl Provider {
+    // vrne ime zamaknjenega zaporedja in request zanj
+    fn ammend(&self, request: &sequence_provide::Request) -> Result<(String, sequence_provide::Request)> {
         let drop_count = request.parameters[0].trunc();
         if drop_count < 0. { return Err(Error::sequence_arithmetic_error(self.get_info(), "Parameter mora biti pozitiven")); }
         let drop_count = drop_count as u64;
 
-        let sequence = request.sequences[0].clone();
+        let sequence = &request.sequences[0];
 
-        let ammended = sequence_provide::Request {
-            range: sequence_provide::Range { from: request.range.from+drop_count, to: request.range.to+drop_count, step: request.range.step },
-            parameters: sequence.parameters.clone(),
-            sequences: sequence.sequences.clone()
+        let (from, to) = match (request.range.from.checked_add(drop_count), request.range.to.checked_add(drop_count)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(Error::invalid_range())
         };
-
-        manager.read().await.find(&sequence.get_info())?.provide(ammended,&manager).await
+        Ok((sequence.name.clone(), request.sub_request(sequence, sequence_provide::Range { from, to, step: request.range.step })))
     }
 }
\ No newline at end of file
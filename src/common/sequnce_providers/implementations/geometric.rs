@@ -1,7 +1,9 @@
+use num_traits::Pow;
+
 use crate::{
     error::{Error, Result}, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::FunctionSequence
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::{self, Exact}, FunctionSequence}
 };
 pub struct Sequence {}
 impl FunctionSequence for Sequence {
@@ -22,6 +24,21 @@ impl FunctionSequence for Sequence {
             Ok(parameters[0] * parameters[1].powf(n as f64))
         }
     }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn evaluate_exact(&self, parameters: &[Exact], n: u64) -> Result<Exact> {
+        Ok(&parameters[0] * Pow::pow(&parameters[1], n))
+    }
+
+    fn estimate_bits(&self, parameters: &[f64], n: u64) -> u64 {
+        match parameters {
+            [g0, q] => (exact::bits(*g0) + n as f64 * exact::bits(*q)).ceil() as u64,
+            _ => 0
+        }
+    }
 }
 
 #[test]
@@ -1,11 +1,11 @@
-use std::vec;
+use std::sync::Arc;
 
 use nalgebra::{DMatrix, DVector};
 
 use crate::{
     error::Result, 
-    parse::sequence_provide::{self, SequenceInfo}, 
-    sequnce_providers::SequenceProvider
+    parse::sequence_provide::{self, Domain, SequenceInfo}, 
+    sequnce_providers::{exact::Exact, recurrence::{self, Kitamasa}, SequenceProvider, Terms}
 };
 
 /// [ Homogena linearna rekurzija poljubne stopnje ]
@@ -37,7 +37,40 @@ impl SequenceProvider for Provider {
         }
     }
 
+    // potenciranje matrike stane O(k^3 log n), vsak člen pa O(k^2)
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        let degree = self.degree as u64;
+        range.len().saturating_mul(degree * degree).saturating_add(128 * degree * degree * degree)
+    }
+
+    fn estimate_bits(&self, range: sequence_provide::Range, parameters: &[f64]) -> u64 {
+        let (coefficients, initial) = parameters.split_at(self.degree.min(parameters.len()));
+        let factor = coefficients.iter().map(|a| a.abs()).sum();
+        recurrence::estimate_bits(coefficients, factor, initial, range.to)
+    }
+
     fn generate(&self,range:sequence_provide::Range, parameters: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64> > {
+        self.terms(range, parameters)?.collect()
+    }
+
+    fn generate_iter(self: Arc<Self>, range: sequence_provide::Range, parameters: Vec<f64>, _: Vec<Vec<f64>>) -> Result<Terms> {
+        Ok(Box::new(self.terms(range, &parameters)?))
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    // točne člene računamo s Kitamasovo metodo, tako da range.from ni omejen na u32
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        let (coefficients, initial) = parameters.split_at(self.degree);
+        Ok(Kitamasa::exact(coefficients).terms(range, initial))
+    }
+}
+
+impl Provider {
+    // vrne iterator čez člene, ki jih zahteva request
+    fn terms(&self, range: sequence_provide::Range, parameters: &[f64]) -> Result<impl Iterator<Item = Result<f64>> + Send> {
         // ustvarimo matriko rekurzivne zveze, da M.(f(n),f(n+1),...f(n+k-1)) = (f(n+1),f(n+2),...f(n+k))
         let mat: DMatrix<f64> = DMatrix::from_fn(self.degree, self.degree, |i,j| {
             if i < self.degree-1 {
@@ -53,14 +86,11 @@ impl SequenceProvider for Provider {
         let mat = mat.pow(range.step.try_into()?);
 
         // izračunamo vse člene, ki jih zahteva request
-        let mut result = vec![];        
-        let mut i = range.from; 
-        while i < range.to {
-            result.push(inital[0]);
-            inital = (&mat) * inital;
-            i += range.step;
-        }
-        Ok(result)
+        Ok((0..range.len()).map(move |_| {
+            let term = inital[0];
+            inital = &mat * &inital;
+            Ok(term)
+        }))
     }
 }
 
@@ -77,4 +107,8 @@ fn test() {
         fs.generate(sequence_provide::Range { from: 1, to: 10, step: 1 }, &[1.,1.,0.,1.], &[]),
         Ok(vec![1.,1.,2.,3.,5.,8.,13.,21.,34.])
     );
+
+    let parameters = [1, 1, 0, 1].map(crate::sequnce_providers::exact::from_integer);
+    let terms = fs.generate_exact(sequence_provide::Range { from: 100, to: 101, step: 1 }, Domain::Integer, &parameters, &[]).unwrap();
+    assert_eq!(crate::sequnce_providers::exact::to_strings(&terms), vec!["354224848179261915075"]);
 }
\ No newline at end of file
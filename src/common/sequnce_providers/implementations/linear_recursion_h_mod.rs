@@ -0,0 +1,108 @@
+use num_integer::Integer;
+use num_traits::ToPrimitive;
+
+use crate::{
+    error::{Error, Result},
+    parse::sequence_provide::{self, Domain, SequenceInfo},
+    sequnce_providers::{exact::{self, Exact}, modular::Residue, recurrence::Kitamasa, SequenceProvider}
+};
+
+/// [ Homogena linearna rekurzija poljubne stopnje po modulu M ]
+pub struct Provider { degree: usize }
+
+impl Provider {
+    pub fn new(degree: usize) -> Self { Self {degree} }
+
+    // parametri [a1,...,ak,f0,...,f_{k-1}] so ostanki po modulu M, ki je zadnji parameter
+    fn terms(&self, range: sequence_provide::Range, parameters: &[i128], modulus: u64) -> Vec<u64> {
+        let residues: Vec<Residue> = parameters.iter().map(|parameter| Residue::new(*parameter, modulus)).collect();
+        let (coefficients, initial) = residues.split_at(self.degree);
+
+        let recurrence = Kitamasa::new(coefficients.to_vec(), Residue::new(0, modulus), Residue::new(1, modulus));
+        recurrence.terms(range, initial).iter().map(Residue::value).collect()
+    }
+
+    fn invalid_parameters(&self) -> Error {
+        Error::sequence_arithmetic_error(self.get_info(), "Parametri morajo biti cela števila, manjša od 2^64 po absolutni vrednosti, in 0 < M.")
+    }
+}
+
+impl SequenceProvider for Provider {
+
+    fn get_info(&self) -> SequenceInfo {
+        let (degree, degree_m) = (self.degree, self.degree-1);
+        let description = {
+            if degree == 1 {
+                "Homogeno linearno rekurzivno zaporedje po modulu M, oblike: f(n) = a_1 f(n-1) mod M \
+                z robnimi pogoji f(0) = f0, Paramteri: [a1,f0,M]".to_owned()
+            } else {
+                format!("Homogeno linearno rekurzivno zaporedje po modulu M, oblike: f(n) = a_1 f(n-1) + ... + a_{degree} f(n-{degree}) mod M \
+                z robnimi pogoji f(0) = f0, ..., f({degree_m}) = f_{degree_m}. Paramteri: [a1,...,a{degree},f0,...,f_{degree_m},M]")
+            }
+        };
+
+        SequenceInfo {
+            name: "linear_rec_h_mod".to_owned(),
+            description,
+            parameters: 2*self.degree + 1,
+            sequences: 0
+        }
+    }
+
+    // prvi člen stane O(k^2 log n), vsak naslednji pa O(k^2)
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        let degree = self.degree as u64;
+        range.len().saturating_add(128).saturating_mul(degree * degree)
+    }
+
+    fn generate(&self, range: sequence_provide::Range, parameters: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64>> {
+        let bound = 2f64.powi(64);
+        if parameters.iter().any(|parameter| !(0. ..bound).contains(&parameter.trunc().abs())) { return Err(self.invalid_parameters()); }
+
+        let parameters: Vec<i128> = parameters.iter().map(|parameter| parameter.trunc() as i128).collect();
+        let (parameters, modulus) = parameters.split_at(2*self.degree);
+        let modulus = u64::try_from(modulus[0]).ok().filter(|modulus| *modulus > 0).ok_or(self.invalid_parameters())?;
+
+        Ok(self.terms(range, parameters, modulus).into_iter().map(|term| term as f64).collect())
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer]
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        let (parameters, modulus) = parameters.split_at(2*self.degree);
+        let modulus = modulus[0].to_integer();
+        let Some(m @ 1..) = modulus.to_u64() else { return Err(self.invalid_parameters()) };
+
+        // parametre najprej zmanjšamo po modulu, tako da so lahko poljubno veliki
+        let parameters: Vec<i128> = parameters.iter()
+            .map(|parameter| parameter.to_integer().mod_floor(&modulus).to_i128().unwrap_or_default())
+            .collect();
+        Ok(self.terms(range, &parameters, m).into_iter().map(exact::from_integer).collect())
+    }
+}
+
+#[test]
+fn test() {
+    let fs = Provider::new(2);
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 0, to: 10, step: 1 }, &[1., 1., 0., 1., 10.], &[]),
+        Ok(vec![0., 1., 1., 2., 3., 5., 8., 3., 1., 4.])
+    );
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 1_000_000_000_000_000, to: 1_000_000_000_000_003, step: 1 }, &[1., 1., 0., 1., 1_000_000_007.], &[]),
+        Ok(vec![648_325_137., 133_295_545., 781_620_682.])
+    );
+    for parameters in [[1., 1., 0., 1., 0.], [1., 1., 0., 1., -5.], [1., f64::INFINITY, 0., 1., 7.], [1., 1., 0., 1., f64::NAN]] {
+        assert!(fs.generate(sequence_provide::Range { from: 0, to: 3, step: 1 }, &parameters, &[]).is_err());
+    }
+
+    // modul blizu 2^64 in negativni koeficienti
+    let fs = Provider::new(3);
+    let parameters = [1, -1, 1, 1, -2, 5].into_iter().map(exact::from_integer)
+        .chain([exact::from_integer(18_446_744_073_709_551_557u64)])
+        .collect::<Vec<_>>();
+    let terms = fs.generate_exact(sequence_provide::Range { from: 3, to: 6, step: 1 }, Domain::Integer, &parameters, &[]).unwrap();
+    assert_eq!(exact::to_strings(&terms), vec!["8", "1", "18446744073709551555"]);
+}
@@ -0,0 +1,100 @@
+use crate::{
+    error::{Error, Result},
+    parse::sequence_provide::{self, Domain, SequenceInfo},
+    sequnce_providers::{exact::Exact, recurrence::{self, Ring}, SequenceProvider}
+};
+
+/// [ Nehomogena linearna rekurzija poljubne stopnje ]
+pub struct Provider { degree: usize }
+
+impl Provider {
+    pub fn new(degree: usize) -> Self { Self {degree} }
+
+    // zaporedje g dobimo na range-u 0..range.to, tako da je g[n] = g(n)
+    fn terms<T: Ring>(&self, range: sequence_provide::Range, parameters: &[T], sequences: &[Vec<T>]) -> Result<Vec<T>> {
+        let g = &sequences[0];
+        if g.len() as u64 != range.to {
+            return Err(Error::sequence_arithmetic_error(self.get_info(), "Pridobljena dolžina zaporedja g se ne ujema"));
+        }
+
+        let (coefficients, initial) = parameters.split_at(self.degree);
+        Ok(recurrence::iterate(initial, range, |n, previous| {
+            coefficients.iter().zip(previous.iter().rev()).fold(g[n as usize].clone(), |sum, (a, f)| sum.add(&a.mul(f)))
+        }))
+    }
+}
+
+impl SequenceProvider for Provider {
+
+    fn get_info(&self) -> SequenceInfo {
+        let (degree, degree_m) = (self.degree, self.degree-1);
+        let description = {
+            if degree == 1 {
+                "Nehomogeno linearno rekurzivno zaporedje, oblike: f(n) = a_1 f(n-1) + g(n) \
+                z robnimi pogoji f(0) = f0, Paramteri: [a1,f0], Zaporedja: [g]".to_owned()
+            } else {
+                format!("Nehomogeno linearno rekurzivno zaporedje, oblike: f(n) = a_1 f(n-1) + ... + a_{degree} f(n-{degree}) + g(n) \
+                z robnimi pogoji f(0) = f0, ..., f({degree_m}) = f_{degree_m}. Paramteri: [a1,...,a{degree},f0,...,f_{degree_m}], Zaporedja: [g]")
+            }
+        };
+
+        SequenceInfo {
+            name: "linear_rec_nh".to_owned(),
+            description,
+            parameters: 2*self.degree,
+            sequences: 1
+        }
+    }
+
+    // člene računamo enega za drugim od začetka, vsak stane O(k)
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        range.to.saturating_mul(self.degree as u64)
+    }
+
+    // f(n) je vsota členov g(m), pomnoženih z največ (|a_1| + ... + |a_k|)^(n-m), velikost g pa omeji že njegova zahteva
+    fn estimate_bits(&self, range: sequence_provide::Range, parameters: &[f64]) -> u64 {
+        let (coefficients, initial) = parameters.split_at(self.degree.min(parameters.len()));
+        let factor = coefficients.iter().map(|a| a.abs()).sum();
+        recurrence::estimate_bits(coefficients, factor, initial, range.to).saturating_add(128)
+    }
+
+    // za člen f(n) potrebujemo g(n), zato g pridobimo za vse indekse do range.to
+    fn sequences_range(&self, range: sequence_provide::Range) -> sequence_provide::Range {
+        sequence_provide::Range { from: 0, to: range.to, step: 1 }
+    }
+
+    fn generate(&self, range: sequence_provide::Range, parameters: &[f64], sequences: &[Vec<f64>]) -> Result<Vec<f64>> {
+        self.terms(range, parameters, sequences)
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], sequences: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        self.terms(range, parameters, sequences)
+    }
+}
+
+#[test]
+fn test() {
+    // f(n) = 2 f(n-1) + n, f(0) = 0
+    let fs = Provider::new(1);
+    let g: Vec<Vec<f64>> = vec![(0..8).map(|n| n as f64).collect()];
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 0, to: 8, step: 1 }, &[2., 0.], &g),
+        Ok(vec![0., 1., 4., 11., 26., 57., 120., 247.])
+    );
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 3, to: 8, step: 2 }, &[2., 0.], &g),
+        Ok(vec![11., 57., 247.])
+    );
+    assert!(fs.generate(sequence_provide::Range { from: 0, to: 8, step: 1 }, &[2., 0.], &[vec![1.; 3]]).is_err());
+
+    // f(n) = f(n-1) + f(n-2) + 1 z robnimi pogoji 1, 1, točno za velike n
+    let fs = Provider::new(2);
+    let parameters = [1, 1, 1, 1].map(crate::sequnce_providers::exact::from_integer);
+    let g = vec![crate::sequnce_providers::exact::from_integer(1); 101];
+    let terms = fs.generate_exact(sequence_provide::Range { from: 100, to: 101, step: 1 }, Domain::Integer, &parameters, &[g]).unwrap();
+    assert_eq!(crate::sequnce_providers::exact::to_strings(&terms), vec!["1146295688027634168201"]);
+}
@@ -1,7 +1,7 @@
 use crate::{
     error::Result, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::OperationSequence
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::Exact, OperationSequence}
 };
 
 pub struct Sequence {}
@@ -20,6 +20,14 @@ impl OperationSequence for Sequence {
         Ok(sequences[0].min(sequences[1]))
     }
 
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn apply_exact(&self, _parameters: &[Exact], sequences: &[Exact]) -> Result<Exact> {
+        Ok((&sequences[0]).min(&sequences[1]).clone())
+    }
+
 }
 
 #[test]
@@ -17,11 +17,11 @@ impl Provider {
         let mut building_blocks = BinaryHeap::new();
 
         // dodamo produkte fermata
-        for i in 0..(2 as u8).pow(fermat.len() as u32) {
+        for i in 0..2_u8.pow(fermat.len() as u32) {
             let mut val = 1.;
-            for j in 0..fermat.len() {
+            for (j, f) in fermat.iter().enumerate() {
                 if Self::get_bit(i, j as u8) {
-                    val *= fermat[j];
+                    val *= f;
                 }
             }
             building_blocks.push(Reverse(OrderableF64(val)));
@@ -40,6 +40,11 @@ impl SequenceProvider for Provider {
         }
     }
 
+    // členov ne moremo preskočiti, zato je cena odvisna od range.to
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        range.to
+    }
+
     // Uporabimo min-heap da v O(n log m) časa izračunamo člene od 1..n, kjer je m število gradnikov
     fn generate(&self,range:sequence_provide::Range, _: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64> > {
         let mut heap = self.building_blocks.clone(); // dokaj majheno
@@ -51,7 +56,7 @@ impl SequenceProvider for Provider {
                 .ok_or(Error::sequence_arithmetic_error(self.get_info(), "Prazen min-heap, nepričakovana napaka."))?.0.0;
             
             // če je v našem range-u, ga dodamo, potem pa v heap dodamo dvakratnik
-            if range.from <= i && (i-range.from) % range.step == 0 { result.push(top); } 
+            if range.from <= i && (i - range.from).is_multiple_of(range.step) { result.push(top); } 
             heap.push(Reverse(OrderableF64(2. * top)));
 
             i += 1;
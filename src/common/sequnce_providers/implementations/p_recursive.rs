@@ -0,0 +1,91 @@
+use crate::{
+    error::Result,
+    parse::sequence_provide::{self, Domain, SequenceInfo},
+    sequnce_providers::{exact::{self, Exact}, recurrence::{self, Ring}, SequenceProvider}
+};
+
+/// [ P-rekurzivno zaporedje: linearna rekurzija s polinomskimi koeficienti ]
+pub struct Provider { order: usize, degree: usize }
+
+impl Provider {
+    pub fn new(order: usize, degree: usize) -> Self { Self {order, degree} }
+
+    // n, ki ga vstavimo v polinome, je v istem kolobarju kot členi
+    fn terms<T: Ring>(&self, range: sequence_provide::Range, parameters: &[T], zero: T, index: impl Fn(u64) -> T) -> Vec<T> {
+        let (polynomials, initial) = parameters.split_at(self.order * (self.degree + 1));
+        recurrence::iterate(initial, range, |n, previous| {
+            let n = index(n);
+            polynomials.chunks(self.degree + 1).zip(previous.iter().rev()).fold(zero.clone(), |sum, (polynomial, f)| {
+                // Hornerjev postopek za p_i(n)
+                let value = polynomial.iter().rev().fold(zero.clone(), |value, c| value.mul(&n).add(c));
+                sum.add(&value.mul(f))
+            })
+        })
+    }
+}
+
+impl SequenceProvider for Provider {
+
+    fn get_info(&self) -> SequenceInfo {
+        let (order, order_m, degree) = (self.order, self.order-1, self.degree);
+        let description = format!(
+            "P-rekurzivno zaporedje, oblike: f(n) = p_1(n) f(n-1) + ... + p_{order}(n) f(n-{order}), kjer je \
+            p_i(n) = c_i0 + c_i1 n + ... + c_i{degree} n^{degree}, z robnimi pogoji f(0) = f0, ..., f({order_m}) = f_{order_m}. \
+            Paramteri: [c_10,...,c_1{degree},...,c_{order}0,...,c_{order}{degree},f0,...,f_{order_m}]"
+        );
+
+        SequenceInfo {
+            name: "p_recursive".to_owned(),
+            description,
+            parameters: self.order * (self.degree + 2),
+            sequences: 0
+        }
+    }
+
+    // člene računamo enega za drugim od začetka, vsak stane O(k d)
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        range.to.saturating_mul((self.order * (self.degree + 1)) as u64)
+    }
+
+    // |p_i(n)| je največ (|c_i0| + ... + |c_id|) n^d
+    fn estimate_bits(&self, range: sequence_provide::Range, parameters: &[f64]) -> u64 {
+        let (polynomials, initial) = parameters.split_at((self.order * (self.degree + 1)).min(parameters.len()));
+        let factor = polynomials.iter().map(|c| c.abs()).sum::<f64>() * (range.to as f64).powi(self.degree as i32);
+        recurrence::estimate_bits(polynomials, factor, initial, range.to)
+    }
+
+    fn generate(&self, range: sequence_provide::Range, parameters: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64>> {
+        Ok(self.terms(range, parameters, 0., |n| n as f64))
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        Ok(self.terms(range, parameters, exact::zero(), exact::from_integer))
+    }
+}
+
+#[test]
+fn test() {
+    // f(n) = n f(n-1), f(0) = 1
+    let fs = Provider::new(1, 1);
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 0, to: 7, step: 1 }, &[0., 1., 1.], &[]),
+        Ok(vec![1., 1., 2., 6., 24., 120., 720.])
+    );
+
+    // f(n) = (n + 1) f(n-1) + (n^2 - 1) f(n-2)
+    let fs = Provider::new(2, 2);
+    assert_eq!(
+        fs.generate(sequence_provide::Range { from: 1, to: 8, step: 2 }, &[1., 1., 0., -1., 0., 1., 1., 2.], &[]),
+        Ok(vec![2., 52., 3618., 486872.])
+    );
+
+    // število permutacij brez negibnih točk, D(n) = (n - 1) (D(n-1) + D(n-2))
+    let fs = Provider::new(2, 1);
+    let parameters = [-1, 1, -1, 1, 1, 0].map(exact::from_integer);
+    let terms = fs.generate_exact(sequence_provide::Range { from: 30, to: 31, step: 1 }, Domain::Integer, &parameters, &[]).unwrap();
+    assert_eq!(exact::to_strings(&terms), vec!["97581073836835777732377428235481"]);
+}
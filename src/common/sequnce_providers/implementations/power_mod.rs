@@ -1,40 +1,87 @@
+use num_traits::ToPrimitive;
+
 use crate::{
-    error::{Error, Result}, 
-    parse::sequence_provide::{self, SequenceInfo}, 
-    sequnce_providers::SequenceProvider
+    error::{Error, Result},
+    parse::sequence_provide::{self, Domain, SequenceInfo},
+    sequnce_providers::{exact::{self, Exact}, modular, SequenceProvider}
 };
 
+// vsak praštevilski eksponent v razcepu M < 2^64 je manjši od 64, zato za e >= 64 velja a^e = a^(e mod φ(M) + φ(M)) (mod M)
+const EULER_THRESHOLD: u128 = 64;
+
 pub struct Provider {}
+
+impl Provider {
+    // p^k, če je manjši od EULER_THRESHOLD, sicer p^k mod φ + φ; v obeh primerih je a^e = a^(p^k) (mod M)
+    fn exponent(p: u64, k: u64, phi: u64) -> u128 {
+        match p {
+            0 => u128::from(k == 0),
+            1 => 1,
+            _ => {
+                let mut power: u128 = 1;
+                for _ in 0..k {
+                    power *= p as u128;
+                    if power >= EULER_THRESHOLD {
+                        return modular::pow_mod(p, k as u128, phi) as u128 + phi as u128;
+                    }
+                }
+                power
+            }
+        }
+    }
+
+    // f(from) izračunamo direktno, nato pa vsak naslednji člen kot f(n + step) = f(n)^(p^step) mod M
+    fn terms(&self, range: sequence_provide::Range, a: u64, p: u64, m: u64) -> Vec<u64> {
+        let phi = modular::totient(m);
+        let step = Provider::exponent(p, range.step, phi);
+
+        let mut term = modular::pow_mod(a, Provider::exponent(p, range.from, phi), m);
+        (0..range.len()).map(|_| {
+            let current = term;
+            term = modular::pow_mod(term, step, m);
+            current
+        }).collect()
+    }
+
+    fn invalid_parameters(&self) -> Error {
+        Error::sequence_arithmetic_error(self.get_info(), "Neveljavni parametri, veljati mora 0 <= a, p in 0 < M < 2^64.")
+    }
+}
+
 impl SequenceProvider for Provider {
 
     fn get_info(&self) -> SequenceInfo {
         SequenceInfo {
             name: "power_mod".to_owned(),
-            description: "f(n) = (a^(p^n) mod M), Parametri [a >= 0, p >= 0, M > 0] so truncirani u32.". to_owned(),
+            description: "f(n) = (a^(p^n) mod M), Parametri [a >= 0, p >= 0, M > 0] so truncirani na cela števila, manjša od 2^64.". to_owned(),
             parameters: 3,
             sequences: 0
         }
     }
 
+    // razcep M stane največ okoli 2^16 korakov, vsak člen pa eno potenciranje z eksponentom pod 2^65
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        range.len().saturating_mul(128).saturating_add(1 << 17)
+    }
+
     fn generate(&self, range: sequence_provide::Range, parameters: &[f64], _sequences: &[Vec<f64>]) -> Result<Vec<f64>> {
-        let mut result = vec![];
-        if parameters[0] < 0. || parameters[1] < 0. || parameters[2] <= 0.  { return Err(Error::sequence_arithmetic_error(self.get_info(), "Neveljavni parametri")); }        
-        let (mut a, p, m): (u32, u32, u32) = (parameters[0].trunc() as u32, parameters[1].trunc() as u32, parameters[2].trunc() as u32);
-
-        let mut i = 0; 
-        while i < range.from {
-            a = a.wrapping_pow(p) % m;
-            i += 1;
+        let [a, p, m] = [0, 1, 2].map(|i| parameters[i].trunc());
+        if [a, p, m].iter().any(|value| !(0. ..2f64.powi(64)).contains(value)) || m == 0. {
+            return Err(self.invalid_parameters());
         }
 
-        let p = p.pow(range.step.try_into()?);
-        while i < range.to {
-            result.push(a);
-            a = a.wrapping_pow(p) % m;
-            i += range.step;
-        }
+        Ok(self.terms(range, a as u64, p as u64, m as u64).into_iter().map(|term| term as f64).collect())
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer]
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _sequences: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        let [a, p, m] = [0, 1, 2].map(|i| parameters[i].to_integer().to_u64());
+        let (Some(a), Some(p), Some(m @ 1..)) = (a, p, m) else { return Err(self.invalid_parameters()) };
 
-        Ok(result.iter().map(|v| *v as f64).collect())
+        Ok(self.terms(range, a, p, m).into_iter().map(exact::from_integer).collect())
     }
 }
 
@@ -50,4 +97,48 @@ fn test() {
         fs.generate(sequence_provide::Range { from: 0, to: 4, step: 2 }, &[2., 2., 17.], &[]),
         Ok(vec![2.,16.])
     );
-}
\ No newline at end of file
+
+    for parameters in [[-1., 2., 5.], [2., 2., 0.], [2., f64::NAN, 5.], [2., 2., 2f64.powi(64)]] {
+        assert!(fs.generate(sequence_provide::Range { from: 0, to: 4, step: 1 }, &parameters, &[]).is_err());
+    }
+}
+
+#[test]
+fn test_table() {
+    let fs = Provider {};
+    // (a, p, M, from, to, step, členi)
+    type Row = (u64, u64, u64, u64, u64, u64, &'static [u64]);
+    let table: [Row; 14] = [
+        (2, 2, 17, 0, 4, 1, &[2, 4, 16, 1]),
+        (3, 5, 1_000_000_007, 10, 13, 1, &[638_025_225, 469_598_182, 565_669_795]),
+        (2, 3, (1 << 61) - 1, 1_000_000, 1_000_003, 1, &[2, 8, 512]),
+        (7, 2, 18_446_744_073_709_551_557, 1_000_000_000_000_000, 1_000_000_000_000_002, 1, &[9_516_271_636_003_589_508, 14_588_085_553_011_442_560]),
+        (12, 6, 1, 0, 3, 1, &[0, 0, 0]),
+        (0, 0, 5, 0, 3, 1, &[0, 1, 1]),
+        (0, 3, 9, 0, 3, 1, &[0, 0, 0]),
+        (5, 0, 7, 0, 3, 1, &[5, 1, 1]),
+        (6, 10, 1 << 40, 5, 20, 5, &[0, 0, 0]),
+        (123_456_789, 987_654_321, 1_000_000_000_000_000_009, 1_000_000_000_000, 1_000_000_000_003, 1, &[897_446_286_701_321_433, 687_641_831_468_931_573, 305_776_825_253_934_644]),
+        (2, 2, 1 << 32, 3, 8, 1, &[256, 65_536, 0, 0, 0]),
+        (10, 3, 2_999_999_999_967, 7, 10, 1, &[2_499_734_544_499, 2_035_840_597_888, 556_370_611_891]),
+        (9_007_199_254_740_991, 2, 9_007_199_254_740_881, 0, 3, 1, &[110, 12_100, 146_410_000]),
+        (3, 1, 10, 0, 1_000_000_000, 250_000_000, &[3, 3, 3, 3])
+    ];
+
+    for (a, p, m, from, to, step, expected) in table {
+        let range = sequence_provide::Range { from, to, step };
+        let terms = fs.generate_exact(range, Domain::Integer, &[a, p, m].map(exact::from_integer), &[]).unwrap();
+        assert_eq!(terms, expected.iter().map(|&term| exact::from_integer(term)).collect::<Vec<_>>(), "a = {a}, p = {p}, M = {m}");
+    }
+
+    // primerjamo z neposrednim potenciranjem z eksponentom p^n
+    for (a, p, m) in [(2u32, 2u32, 17u32), (6, 4, 1 << 20), (10, 3, 63), (0, 5, 12), (7, 0, 9), (123, 7, 1000)] {
+        let range = sequence_provide::Range { from: 0, to: 12, step: 1 };
+        let terms = fs.generate(range, &[a, p, m].map(f64::from), &[]).unwrap();
+        let direct: Vec<f64> = (0..12u32).map(|n| {
+            let exponent = num_bigint::BigUint::from(p).pow(n);
+            num_bigint::BigUint::from(a).modpow(&exponent, &num_bigint::BigUint::from(m)).try_into().map(|term: u64| term as f64).unwrap()
+        }).collect();
+        assert_eq!(terms, direct, "a = {a}, p = {p}, M = {m}");
+    }
+}
@@ -17,7 +17,7 @@ impl OperationSequence for Sequence {
     }
 
     fn apply(&self, parameters: &[f64], sequences: &[f64]) -> Result<f64> {
-        let factor = (10 as f64).powf(parameters[0]);
+        let factor = 10_f64.powf(parameters[0]);
         Ok((factor * sequences[0]).round()/factor)
     }
 }
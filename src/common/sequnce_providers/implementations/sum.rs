@@ -1,7 +1,7 @@
 use crate::{
     error::Result, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::OperationSequence
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::Exact, OperationSequence}
 };
 
 pub struct Sequence {}
@@ -19,6 +19,14 @@ impl OperationSequence for Sequence {
     fn apply(&self, _parameters: &[f64], sequences: &[f64]) -> Result<f64> {
         Ok(sequences[0] + sequences[1])
     }
+
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float, Domain::Integer, Domain::Rational]
+    }
+
+    fn apply_exact(&self, _parameters: &[Exact], sequences: &[Exact]) -> Result<Exact> {
+        Ok(&sequences[0] + &sequences[1])
+    }
 }
 
 #[test]
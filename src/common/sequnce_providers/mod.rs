@@ -1,160 +1,551 @@
 pub mod variants;
+pub mod cache;
+pub mod exact;
+pub mod modular;
+pub mod recurrence;
+pub mod health;
 mod implementations;
 
-use std::vec;
+use std::{future::Future, sync::{Arc, Mutex}, time::{Duration, Instant}, vec};
+use futures::{stream, StreamExt, TryStreamExt};
 use rand::seq::SliceRandom;
 use async_trait::async_trait;
-use tokio::sync::RwLock;
+use tokio::{sync::{mpsc, RwLock}, time::{timeout, timeout_at}};
 
 use crate::{
     error::{Error, Result}, 
     parse::{
-        parse_helper::Sendable, remote::Remote, 
-        sequence_provide::{self, SequenceInfo},
+        encoding::{Encoding, ENCODINGS_HEADER}, parse_helper::Sendable, remote::Remote, 
+        sequence_provide::{self, CatalogEntry, Domain, RecurrenceQuery, SequenceInfo, SequenceParameter},
         settings::SettingsPonudnik
     }
 };
 
 use variants::{function::*, operation::*};
+use cache::{CacheStats, SequenceCache};
+use health::{HealthReport, HealthTracker};
 use implementations::*;
+use exact::Exact;
+
+/// Členi zaporedja, ki jih ponudnik računa sproti
+pub type Terms = Box<dyn Iterator<Item = Result<f64>> + Send>;
+
+/// Kosi členov, ki jih `ProviderManager::provide_stream` računa na ločeni niti
+pub type Batches = mpsc::Receiver<Result<Vec<f64>>>;
+
+/// Število členov v enem kosu pri pretakanju
+pub const STREAM_BATCH: usize = 1024;
 
 /// Ponudnik neke vrste zaporedja
 #[async_trait]
-pub trait SequenceProvider : Sync {
+pub trait SequenceProvider : Send + Sync + 'static {
     /// Vrne informacije o zaporedju
     fn get_info(&self) -> sequence_provide::SequenceInfo;
     /// Generira zaporedje iz delov
     fn generate(&self, range: sequence_provide::Range, parameters: &[f64], sequences: &[Vec<f64>]) -> Result<Vec<f64>>;
+    /// Oceni ceno izračuna zaporedja (brez podzaporedij) kot približno število operacij
+    fn estimate_cost(&self, range: sequence_provide::Range) -> u64 {
+        range.len()
+    }
+    /// Oceni število bitov največjega člena na `range` pri točnem računanju s parametri `parameters`. Privzeto
+    /// predpostavi, da členi niso dosti večji od parametrov (velikost podzaporedij omejijo že njihove zahteve).
+    fn estimate_bits(&self, _range: sequence_provide::Range, parameters: &[f64]) -> u64 {
+        parameters.iter().map(|parameter| exact::bits(*parameter)).fold(0., f64::max) as u64 + 64
+    }
+    /// Generira zaporedje člen po člen. Privzeto najprej izračuna celotno zaporedje z generate, 
+    /// ponudniki, ki lahko člene računajo sproti, pa to povozijo.
+    fn generate_iter(self: Arc<Self>, range: sequence_provide::Range, parameters: Vec<f64>, sequences: Vec<Vec<f64>>) -> Result<Terms> {
+        Ok(Box::new(self.generate(range, &parameters, &sequences)?.into_iter().map(Ok)))
+    }
+    /// Range, na katerem potrebujemo podzaporedja, da izračunamo zaporedje na `range` (privzeto kar `range`)
+    fn sequences_range(&self, range: sequence_provide::Range) -> sequence_provide::Range {
+        range
+    }
 
-    /// Najprej pridobi potrebna zaporedja in potem pokliče generate, da združi v eno
-    async fn provide(&self, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
-        let mut sequences = vec![];
-        for seq in &request.sequences {
-            let result = manager.read().await.find(&seq.get_info())?.provide(
-                sequence_provide::Request { range: request.range, parameters: seq.parameters.clone(), sequences: seq.sequences.clone() },
-                &manager
-            ).await?;
-            sequences.push(result);
-        }
+    /// Domene, v katerih zna ponudnik računati (privzeto le `Float`)
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float]
+    }
+    /// Točno generira zaporedje v domeni `Integer` ali `Rational`, če jo ponudnik podpira
+    fn generate_exact(&self, _range: sequence_provide::Range, domain: Domain, _parameters: &[Exact], _sequences: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        Err(Error::unsupported_domain(self.get_info(), domain))
+    }
+
+    /// Najprej (hkrati) pridobi potrebna zaporedja in potem pokliče generate, da združi v eno
+    async fn provide(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
+        let sequences = ProviderManager::provide_sequences(&request, self.sequences_range(request.range), manager).await?;
+        ProviderManager::compute(move || self.generate(request.range, &request.parameters, &sequences)).await
+    }
 
-        self.generate(request.range, &request.parameters, &sequences)
+    /// Kot provide, le da vrne člene, ki se računajo sproti (z generate_iter). Ponudniki, ki povozijo provide, 
+    /// morajo povoziti tudi to, saj pretakanje sicer obide njihov provide.
+    async fn provide_iter(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Terms> {
+        let sequences = ProviderManager::provide_sequences(&request, self.sequences_range(request.range), manager).await?;
+        ProviderManager::compute(move || self.generate_iter(request.range, request.parameters, sequences)).await
     }
+
+    /// Kot provide, le da zaporedje izračuna točno v domeni requesta
+    async fn provide_exact(self: Arc<Self>, request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<Exact>> {
+        let sequences = ProviderManager::provide_sequences_exact(&request, self.sequences_range(request.range), manager).await?;
+        let parameters = request.parameters.iter().map(|parameter| exact::from_f64(*parameter, request.domain)).collect::<Result<Vec<_>>>()?;
+        ProviderManager::compute(move || self.generate_exact(request.range, request.domain, &parameters, &sequences)).await
+    }
+}
+/// Ponudniki, ki jih najde `ProviderManager::find`
+pub enum Found {
+    Local(Arc<dyn SequenceProvider + Send>),
+    Remote(Vec<RemoteSequenceProvider>)
 }
+
 /// Struktura, ki hrani vse ponudnike zaporedij
 pub struct ProviderManager {
-    pub local_providers:    Vec<Box<dyn SequenceProvider + Send>>,
-    remote_providers:   Vec<Box<dyn SequenceProvider + Send>>,
+    pub local_providers:    Vec<Arc<dyn SequenceProvider + Send>>,
+    /// Najmanjše število členov, pri katerem odgovor pošiljamo po kosih
+    pub stream_threshold:   u64,
+    remote_providers:   Vec<RemoteSequenceProvider>,
     generator:          Remote,
-    central:            Remote
+    central:            Remote,
+    max_hops:           usize,
+    concurrency:        usize,
+    retries:            usize,
+    remote_timeout:     Duration,
+    max_terms:          u64,
+    max_cost:           u64,
+    max_bits:           u64,
+    max_degree:         usize,
+    max_deadline:       Duration,
+    cache:              Mutex<SequenceCache>,
+    health:             Mutex<HealthTracker>
 }
 
 impl ProviderManager {
     pub fn new(settings: &SettingsPonudnik, generator: &Remote, central: &Remote) -> Self {
 
-        let mut local_providers: Vec<Box<dyn SequenceProvider + Send>> = vec![
-                Box::new(constant::Provider {}),
-                Box::new(drop::Provider {}),
-                Box::new(power_mod::Provider {}),
-                Box::new(p_euler::Provider::new()),
-                Box::new(OperationSequenceProvider::new(Box::new(sum::Sequence {}))),
-                Box::new(OperationSequenceProvider::new(Box::new(prod::Sequence {}))),
-                Box::new(OperationSequenceProvider::new(Box::new(lin_com::Sequence {}))),
-                Box::new(OperationSequenceProvider::new(Box::new(round::Sequence {}))),
-                Box::new(OperationSequenceProvider::new(Box::new(max_seqs::Sequence {}))),
-                Box::new(OperationSequenceProvider::new(Box::new(min_seqs::Sequence {}))),
-                Box::new(FunctionSequenceProvider::new(Box::new(arithmetic::Sequence {}))),
-                Box::new(FunctionSequenceProvider::new(Box::new(geometric::Sequence {}))),
+        let mut local_providers: Vec<Arc<dyn SequenceProvider + Send>> = vec![
+                Arc::new(constant::Provider {}),
+                Arc::new(drop::Provider {}),
+                Arc::new(power_mod::Provider {}),
+                Arc::new(p_euler::Provider::new()),
+                Arc::new(OperationSequenceProvider::new(Box::new(sum::Sequence {}))),
+                Arc::new(OperationSequenceProvider::new(Box::new(prod::Sequence {}))),
+                Arc::new(OperationSequenceProvider::new(Box::new(lin_com::Sequence {}))),
+                Arc::new(OperationSequenceProvider::new(Box::new(round::Sequence {}))),
+                Arc::new(OperationSequenceProvider::new(Box::new(max_seqs::Sequence {}))),
+                Arc::new(OperationSequenceProvider::new(Box::new(min_seqs::Sequence {}))),
+                Arc::new(FunctionSequenceProvider::new(Box::new(arithmetic::Sequence {}))),
+                Arc::new(FunctionSequenceProvider::new(Box::new(geometric::Sequence {}))),
         ];
 
         if settings.lin_recur_globina > 0 {
             for i in 1..=settings.lin_recur_globina {
-                local_providers.push(Box::new(linear_recursion_h::Provider::new(i as usize)))
+                local_providers.push(Arc::new(linear_recursion_h::Provider::new(i as usize)));
+                local_providers.push(Arc::new(linear_recursion_h_mod::Provider::new(i as usize)));
+                local_providers.push(Arc::new(linear_recursion_nh::Provider::new(i as usize)));
+                local_providers.push(Arc::new(p_recursive::Provider::new(i as usize, settings.p_rec_stopnja as usize)));
             }
         }
 
         ProviderManager { 
             local_providers,
+            stream_threshold: settings.prag_pretakanja,
             remote_providers: vec![],
             generator: generator.clone(),
-            central: central.clone()
+            central: central.clone(),
+            max_hops: settings.max_skokov,
+            concurrency: settings.vzporedne_zahteve.max(1),
+            retries: settings.poskusi.max(1),
+            remote_timeout: Duration::from_secs(settings.timeout_remote),
+            max_terms: settings.max_clenov,
+            max_cost: settings.max_cena,
+            max_bits: settings.max_bitov,
+            max_degree: settings.lin_recur_globina as usize,
+            max_deadline: Duration::from_secs(settings.rok),
+            cache: Mutex::new(SequenceCache::new(settings.cache_velikost, Duration::from_secs(settings.cache_ttl))),
+            health: Mutex::new(HealthTracker::new(settings.prag_napak, Duration::from_secs(settings.cas_izlocitve)))
         }
 
     }
 
-    /// Najde ponudnika, ki ponuja zaporedje z podano signaturo
-    pub fn find(&self, seq: &SequenceInfo) -> Result<&Box<dyn SequenceProvider + Send>> {
+    /// Najde ponudnike, ki ponujajo zaporedje z podano signaturo. Če zaporedje ponujamo lokalno, vrne lokalnega 
+    /// ponudnika, sicer pa vse primerne oddaljene, urejene po zdravju (enako zdravi so v naključnem vrstnem redu). Oddaljenih ponudnikov, 
+    /// ki so že na poti `visited` zahteve, ne izbere, saj bi zahteva sicer lahko krožila, prav tako pa ne izbere izločenih.
+    pub fn find(&self, seq: &SequenceInfo, visited: &[String]) -> Result<Found> {
         let mut close = vec![];
         
-        let local = self.local_providers.iter().filter(|provider| {
+        let local = self.local_providers.iter().find(|provider| {
             let info = &provider.get_info();
             if info.name == seq.name { close.push(info.clone()); }
             info == seq
-        }).next();
+        });
 
-        if local.is_some() { Ok(local.unwrap()) }
+        if let Some(local) = local { Ok(Found::Local(local.clone())) }
         else {
-            let valid: Vec<&Box<dyn SequenceProvider + Send>> = self.remote_providers.iter().filter(|provider| {
-                let info = &provider.get_info();
+            let mut valid: Vec<RemoteSequenceProvider> = self.remote_providers.iter().filter(|provider| {
+                let info = &provider.info;
                 if info.name == seq.name { close.push(info.clone()); }
-                info == seq
-            }).collect();
-            valid.choose(&mut rand::thread_rng()).map(|provider| *provider).ok_or(
-                Error::missing_provider(seq.clone(), &close)
-            )
+                info == seq && !visited.contains(&provider.host.get_url())
+            }).cloned().collect();
+
+            if valid.is_empty() {
+                Err(Error::missing_provider(seq.clone(), &close))
+            } else if visited.len() >= self.max_hops {
+                Err(Error::hop_limit_exceeded(seq.clone(), visited, self.max_hops))
+            } else {
+                valid.shuffle(&mut rand::thread_rng());
+                let ejected: Vec<String> = valid.iter().map(|provider| provider.host.get_url()).collect();
+                let valid = self.health.lock().unwrap().select(valid, |provider| &provider.host);
+                if valid.is_empty() { Err(Error::remotes_ejected(seq.clone(), &ejected)) } else { Ok(Found::Remote(valid)) }
+            }
         }
     }
+
+    // nastavi rok zahteve glede na `deadline_ms` in nastavitve, če ga ta še nima
+    async fn set_deadline(request: &mut sequence_provide::Request, manager: &RwLock<Self>) -> Instant {
+        match request.deadline {
+            Some(deadline) => deadline,
+            None => {
+                let max_deadline = manager.read().await.max_deadline;
+                let length = request.deadline_ms.map_or(max_deadline, |ms| Duration::from_millis(ms).min(max_deadline));
+                let deadline = Instant::now() + length;
+                request.deadline = Some(deadline);
+                deadline
+            }
+        }
+    }
+
+    /// Sinhrono računanje členov izvede na niti za blokirajoče naloge, da ne zasede izvajalnika in da rok zahteve
+    /// poteče tudi med računanjem. Računanje po izteku roka teče do konca, a ga omejuje `check_cost`.
+    pub async fn compute<T: Send + 'static>(generate: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+        tokio::task::spawn_blocking(generate).await?
+    }
+
+    // preveri, da zahteva ne presega omejitve števila členov
+    fn check_terms(&self, info: &SequenceInfo, range: sequence_provide::Range) -> Result<()> {
+        let length = range.len();
+        if length > self.max_terms {
+            Err(Error::budget_exceeded(info.clone(), "terms", length, self.max_terms))
+        } else { Ok(()) }
+    }
+
+    // preveri, da lokalni izračun ne presega omejitve cene. V točnih domenah členi rastejo, zato najprej preveri, da ne bodo
+    // imeli več kot `max_bits` bitov, ceno pa pomnoži s številom 64-bitnih besed člena, saj toliko stane vsaka operacija z njim
+    fn check_cost(&self, info: &SequenceInfo, provider: &(dyn SequenceProvider + Send), request: &sequence_provide::Request) -> Result<()> {
+        let mut cost = provider.estimate_cost(request.range);
+        if !request.domain.is_float() {
+            let bits = provider.estimate_bits(request.range, &request.parameters);
+            if bits > self.max_bits {
+                return Err(Error::budget_exceeded(info.clone(), "bits", bits, self.max_bits));
+            }
+            cost = cost.saturating_mul(bits.div_ceil(64));
+        }
+        if cost > self.max_cost {
+            Err(Error::budget_exceeded(info.clone(), "cost", cost, self.max_cost))
+        } else { Ok(()) }
+    }
+
+    /// Poišče najkrajšo linearno rekurzijo podanih členov. Iskanje izvede na niti za blokirajoče naloge znotraj največjega
+    /// roka zahteve in v mejah cene ter števila bitov, vrne pa le rekurzije, ki jih lahko ponudimo kot `linear_rec_h`.
+    pub async fn analyze_recurrence(query: RecurrenceQuery, manager: &RwLock<Self>) -> Result<SequenceParameter> {
+        let (limits, deadline) = {
+            let manager = manager.read().await;
+            let limits = recurrence::AnalysisLimits { max_degree: manager.max_degree, max_bits: manager.max_bits, max_cost: manager.max_cost };
+            (limits, manager.max_deadline)
+        };
+        timeout(deadline, ProviderManager::compute(move || recurrence::find_linear_recurrence(&query, &limits))).await?
+    }
+
+    /// Vrne zaporedje z imenom `name` za podan request. Najprej preveri cache, 
+    /// šele nato zaporedje izračuna lokalno ali pa ga pridobi od remote-a.
+    /// Če request še nima roka, ga nastavi glede na `deadline_ms` in nastavitve.
+    pub async fn provide(name: &str, mut request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Vec<f64>> {
+        let deadline = ProviderManager::set_deadline(&mut request, manager).await;
+        let info = request.get_info(name);
+        match timeout_at(deadline.into(), ProviderManager::provide_before_deadline(name, request, manager)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::deadline_exceeded(info))
+        }
+    }
+
+    async fn provide_before_deadline(name: &str, request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Vec<f64>> {
+        let info = request.get_info(name);
+        manager.read().await.check_terms(&info, request.range)?;
+
+        let key = cache::key(name, &request);
+        if let Some(result) = manager.read().await.cache.lock().unwrap().get(&key) {
+            return Ok(result);
+        }
+
+        // med računanjem in čakanjem na oddaljene ponudnike ne držimo lock-a
+        let found = manager.read().await.find(&info, &request.visited)?;
+        let result = match found {
+            Found::Local(provider) => {
+                manager.read().await.check_cost(&info, provider.as_ref(), &request)?;
+                provider.provide(request, manager).await?
+            },
+            Found::Remote(candidates) => ProviderManager::provide_remote(&candidates, request, manager, |provider, request| async move {
+                Arc::new(provider).provide(request, manager).await
+            }).await?
+        };
+
+        manager.read().await.cache.lock().unwrap().insert(key, result.clone());
+        Ok(result)
+    }
+
+    /// Kot `provide`, le da zaporedje vrne po kosih, ki jih računamo na niti za blokirajoče naloge, medtem ko jih
+    /// pošiljamo. Rok velja tudi med računanjem: preverimo ga med kosi in po izteku namesto naslednjega kosa vrnemo
+    /// napako. Ko prejemnik kanal zapre (npr. ker je odjemalec zaprl povezavo), računanje ustavimo.
+    /// Rezultata ne shranimo v cache.
+    pub async fn provide_stream(name: &str, mut request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Batches> {
+        let deadline = ProviderManager::set_deadline(&mut request, manager).await;
+        let info = request.get_info(name);
+        let mut terms = ProviderManager::provide_terms(name, request, manager).await?;
+
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::task::spawn_blocking(move || loop {
+            if Instant::now() >= deadline {
+                let _ = sender.blocking_send(Err(Error::deadline_exceeded(info)));
+                return;
+            }
+            let batch: Result<Vec<f64>> = terms.by_ref().take(STREAM_BATCH).collect();
+            if matches!(&batch, Ok(batch) if batch.is_empty()) { return; }
+            let failed = batch.is_err();
+            if sender.blocking_send(batch).is_err() || failed { return; }
+        });
+        Ok(receiver)
+    }
+
+    /// Kot `provide`, le da lokalno zaporedje vrne kot člene, ki se računajo sproti, ko jih beremo. Rok velja le do
+    /// začetka računanja členov (pridobitev podzaporedij ali oddaljenega zaporedja), zato člene beremo v `provide_stream`.
+    pub async fn provide_terms(name: &str, mut request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Terms> {
+        let deadline = ProviderManager::set_deadline(&mut request, manager).await;
+        let info = request.get_info(name);
+        match timeout_at(deadline.into(), ProviderManager::provide_terms_before_deadline(name, request, manager)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::deadline_exceeded(info))
+        }
+    }
+
+    async fn provide_terms_before_deadline(name: &str, request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Terms> {
+        let info = request.get_info(name);
+        manager.read().await.check_terms(&info, request.range)?;
+
+        let key = cache::key(name, &request);
+        if let Some(result) = manager.read().await.cache.lock().unwrap().get(&key) {
+            return Ok(Box::new(result.into_iter().map(Ok)));
+        }
+
+        let found = manager.read().await.find(&info, &request.visited)?;
+        match found {
+            Found::Local(provider) => {
+                manager.read().await.check_cost(&info, provider.as_ref(), &request)?;
+                provider.provide_iter(request, manager).await
+            },
+            Found::Remote(candidates) => {
+                let result = ProviderManager::provide_remote(&candidates, request, manager, |provider, request| async move {
+                    Arc::new(provider).provide(request, manager).await
+                }).await?;
+                Ok(Box::new(result.into_iter().map(Ok)))
+            }
+        }
+    }
+
+    /// Hkrati pridobi vsa podzaporedja requesta na podanem range-u
+    pub async fn provide_sequences(request: &sequence_provide::Request, range: sequence_provide::Range, manager: &RwLock<Self>) -> Result<Vec<Vec<f64>>> {
+        let concurrency = manager.read().await.concurrency;
+        
+        let requests: Vec<_> = request.sequences.iter()
+            .map(|seq| ProviderManager::provide(&seq.name, request.sub_request(seq, range), manager))
+            .collect();
+
+        // buffered ohrani vrstni red zaporedij, try_collect pa vrne prvo napako po vrsti
+        stream::iter(requests)
+            .buffered(concurrency)
+            .try_collect().await
+    }
+
+    /// Kot `provide`, le da zaporedje izračuna točno v domeni requesta (`Integer` ali `Rational`). 
+    /// Točnih rezultatov ne shranjujemo v cache.
+    pub async fn provide_exact(name: &str, mut request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Vec<Exact>> {
+        let deadline = ProviderManager::set_deadline(&mut request, manager).await;
+        let info = request.get_info(name);
+        match timeout_at(deadline.into(), ProviderManager::provide_exact_before_deadline(name, request, manager)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::deadline_exceeded(info))
+        }
+    }
+
+    async fn provide_exact_before_deadline(name: &str, request: sequence_provide::Request, manager: &RwLock<Self>) -> Result<Vec<Exact>> {
+        let info = request.get_info(name);
+        manager.read().await.check_terms(&info, request.range)?;
+
+        let found = manager.read().await.find(&info, &request.visited)?;
+        match found {
+            Found::Local(provider) => {
+                if !provider.domains().contains(&request.domain) {
+                    return Err(Error::unsupported_domain(provider.get_info(), request.domain));
+                }
+                manager.read().await.check_cost(&info, provider.as_ref(), &request)?;
+                provider.provide_exact(request, manager).await
+            },
+            Found::Remote(candidates) => ProviderManager::provide_remote(&candidates, request, manager, |provider, request| async move {
+                Arc::new(provider).provide_exact(request, manager).await
+            }).await
+        }
+    }
+
+    /// Kot `provide_sequences`, le v točni domeni requesta
+    pub async fn provide_sequences_exact(request: &sequence_provide::Request, range: sequence_provide::Range, manager: &RwLock<Self>) -> Result<Vec<Vec<Exact>>> {
+        let concurrency = manager.read().await.concurrency;
+        
+        let requests: Vec<_> = request.sequences.iter()
+            .map(|seq| ProviderManager::provide_exact(&seq.name, request.sub_request(seq, range), manager))
+            .collect();
+
+        stream::iter(requests)
+            .buffered(concurrency)
+            .try_collect().await
+    }
+
+    /// Zaporedoma poskusi pridobiti zaporedje od oddaljenih ponudnikov (z `attempt`), dokler en ne uspe 
+    /// ali pa porabimo vse poskuse. Vsak poskus je omejen s timeoutom.
+    async fn provide_remote<T, F, Fut>(candidates: &[RemoteSequenceProvider], request: sequence_provide::Request, manager: &RwLock<Self>, attempt: F) -> Result<T>
+    where
+        F: Fn(RemoteSequenceProvider, sequence_provide::Request) -> Fut,
+        Fut: Future<Output = Result<T>>
+    {
+        let (retries, timeout_length) = {
+            let manager = manager.read().await;
+            (manager.retries, manager.remote_timeout)
+        };
+
+        let mut attempted = vec![];
+        let mut last_error = None;
+        for provider in candidates {
+            if attempted.len() >= retries { break; }
+            // poskusno zahtevo izločenega generatorja porabimo šele, ko ga res poskusimo
+            if !manager.read().await.health.lock().unwrap().admit(&provider.host) { continue; }
+
+            attempted.push(provider.host.get_url());
+            let start = Instant::now();
+            let error = match timeout(timeout_length, attempt(provider.clone(), request.clone())).await {
+                Ok(Ok(result)) => {
+                    manager.read().await.health.lock().unwrap().record_success(&provider.host, start.elapsed());
+                    return Ok(result)
+                },
+                // če je remote zahtevo zavrnil kot napačno, bi jo tudi ostali
+                Ok(Err(error)) if !error.is_retryable() => return Err(error),
+                Ok(Err(error)) => error,
+                Err(elapsed) => elapsed.into()
+            };
+            manager.read().await.health.lock().unwrap().record_failure(&provider.host);
+            last_error = Some(error);
+        }
+
+        let info = request.get_info(&candidates[0].info.name);
+        if attempted.is_empty() {
+            let ejected: Vec<String> = candidates.iter().map(|provider| provider.host.get_url()).collect();
+            return Err(Error::remotes_ejected(info, &ejected));
+        }
+        Err(Error::remote_attempts_failed(info, &attempted, last_error))
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+
+    /// Vrne zdravje vseh oddaljenih generatorjev, od katerih trenutno poznamo kakšno zaporedje
+    pub fn health_reports(&self) -> Vec<HealthReport> {
+        let mut remotes: Vec<&Remote> = self.remote_providers.iter().map(|provider| &provider.host).collect();
+        remotes.sort_by_key(|remote| remote.get_url());
+        remotes.dedup();
+
+        let health = self.health.lock().unwrap();
+        remotes.into_iter().map(|remote| health.report(remote)).collect()
+    }
     
     pub fn get_info(&self) -> Vec<SequenceInfo> {
         self.local_providers.iter().map(|p| p.get_info()).collect()
     }
 
+    /// Med delovanjem doda lokalnega ponudnika, če zaporedja z enako signaturo še ne ponujamo
+    pub fn register_local(&mut self, provider: Arc<dyn SequenceProvider + Send>) -> Result<SequenceInfo> {
+        let info = provider.get_info();
+        if self.local_providers.iter().any(|local| local.get_info() == info) {
+            return Err(Error::sequence_exists(info));
+        }
+
+        self.local_providers.push(provider);
+        Ok(info)
+    }
+
     /// Pridobi ponudnike vseh zaporedij, ki jih ponuja nek Remote 
-    async fn get_remote_sequence_providers(remote: &Remote) -> Result<Vec<Box<dyn SequenceProvider + Send>>> {
-        let mut result: Vec<Box<dyn SequenceProvider + Send>> = vec![];
-        let (reason, status, data) = remote.get("/sequence/", None).await?;
-
-        if (reason, status) == ("OK".to_owned(), 200) {
-            let list: Vec<SequenceInfo> = serde_json::from_slice(&data)?;
-            for info in list {
-                result.push(
-                    Box::new(RemoteSequenceProvider { host: remote.clone(), info: info.clone() })
-                );
-            }
-            Ok(result)
-        } else { Err(Error::remote_invalid_response(&remote.get_url(), &data)) }
+    async fn get_remote_sequence_providers(remote: &Remote) -> Result<Vec<RemoteSequenceProvider>> {
+        let mut result = vec![];
+        let response = remote.get("/sequence/", None).await?;
+        let encoding = Encoding::preferred(response.header(ENCODINGS_HEADER));
+        let data = remote.expect_success(response)?;
+
+        let list: Vec<SequenceInfo> = serde_json::from_slice(&data)?;
+        for info in list {
+            result.push(RemoteSequenceProvider { host: remote.clone(), info: info.clone(), encoding });
+        }
+        Ok(result)
     }
 
-    /// Posodobi vse oddaljene ponudnike zaporedij
+    /// Pridobi ponudnike vseh zaporedij iz kataloga centralnega strežnika
+    async fn get_catalog_sequence_providers(central_server: &Remote) -> Result<Vec<RemoteSequenceProvider>> {
+        let data = central_server.expect_success(central_server.get("/sequences", None).await?)?;
+        let catalog: Vec<CatalogEntry> = serde_json::from_slice(&data)?;
+
+        Ok(catalog.into_iter().flat_map(|entry| {
+            entry.providers.into_iter().map(move |provider| RemoteSequenceProvider {
+                encoding: Encoding::preferred(provider.encodings.as_deref()),
+                host: provider.remote,
+                info: entry.info.clone()
+            })
+        }).collect())
+    }
+
+    /// Posodobi vse oddaljene ponudnike zaporedij. Najprej jih poskusi pridobiti iz kataloga centralnega strežnika, 
+    /// če ta kataloga nima (npr. druga implementacija), pa vpraša vsak registriran generator posebej.
     pub async fn update_providers(register_endpoint: &str, manager: &RwLock<Self>) -> Result<()> {
         let generator = manager.read().await.generator.clone();
         let central_server = manager.read().await.central.clone();      
-        let (reason, status, data) = central_server.get(register_endpoint, None).await?;
-
-        if (reason, status) == ("OK".to_owned(), 200) {
-            let list: Vec<Remote> = serde_json::from_slice(&data)?;
-            let mut providers = vec![];
-            for remote in list {
-                if remote != generator {
-                    match ProviderManager::get_remote_sequence_providers(&remote).await {
-                        Err(_) => (),
-                        Ok(mut extra) => providers.append(&mut extra)
-                    }
-                }    
+
+        let mut providers = match ProviderManager::get_catalog_sequence_providers(&central_server).await {
+            Ok(providers) => providers,
+            Err(_) => {
+                let data = central_server.expect_success(central_server.get(register_endpoint, None).await?)?;
+                let list: Vec<Remote> = serde_json::from_slice(&data)?;
+                let mut providers = vec![];
+                for remote in list {
+                    if remote != generator {
+                        match ProviderManager::get_remote_sequence_providers(&remote).await {
+                            Err(_) => (),
+                            Ok(mut extra) => providers.append(&mut extra)
+                        }
+                    }    
+                }
+                providers
             }
+        };
+        providers.retain(|provider| provider.host != generator);
 
-            let mut manager = manager.write().await;
-            manager.remote_providers = providers;
-            Ok(())
-        } else { Err(Error::remote_invalid_response(&central_server.get_url(),&data)) }
+        let mut manager = manager.write().await;
+        manager.remote_providers = providers;
+        Ok(())
     } 
 }
 
 // ---------- implementacije posebnega primera za oddaljene ponudnike ----------
 
 /// Ponudnik zaporedja, ki ga ponuja nek Remote
-struct RemoteSequenceProvider {
-    host:   Remote,
-    info:   SequenceInfo
+#[derive(Clone)]
+pub struct RemoteSequenceProvider {
+    host:       Remote,
+    info:       SequenceInfo,
+    /// Najbolj učinkovit zapis členov, ki ga oglašuje remote
+    encoding:   Encoding
 }
 
 #[async_trait]
@@ -162,13 +553,448 @@ impl SequenceProvider for RemoteSequenceProvider {
     fn generate(&self,_:sequence_provide::Range,_: &[f64],_: &[Vec<f64>]) -> Result<Vec<f64> > { panic!("Unreachable code!") }
     fn get_info(&self) -> sequence_provide::SequenceInfo { self.info.clone() }
 
-    async fn provide(&self, request: sequence_provide::Request, _: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
+    // zahtevi dodamo sebe na pot, da je naslednji generator ne vrne nazaj k nam, in mu sporočimo preostali čas
+    async fn provide(self: Arc<Self>, mut request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<f64>> {
+        request.visited.push(manager.read().await.generator.get_url());
+        if let Some(deadline) = request.deadline {
+            request.deadline_ms = Some(deadline.saturating_duration_since(Instant::now()).as_millis() as u64);
+        }
+        // zahtevamo najbolj učinkovit zapis, remote pa lahko vseeno odgovori z JSON
+        let endpoint = format!("/sequence/{}", self.info.name);
+        let accept = ("Accept", self.encoding.accept());
+        let response = self.host.post_with_headers(&endpoint, &[accept], &request.as_sendable()?, None).await?;
+
+        let encoding = Encoding::from_content_type(response.header("content-type"));
+        encoding.decode(&self.host.expect_success(response)?)
+    }
+
+    // točne člene remote vedno pošlje kot JSON nize
+    async fn provide_exact(self: Arc<Self>, mut request: sequence_provide::Request, manager: &RwLock<ProviderManager>) -> Result<Vec<Exact>> {
+        request.visited.push(manager.read().await.generator.get_url());
+        if let Some(deadline) = request.deadline {
+            request.deadline_ms = Some(deadline.saturating_duration_since(Instant::now()).as_millis() as u64);
+        }
         let endpoint = format!("/sequence/{}", self.info.name);
-        let (reason, status, data) = self.host.post(&endpoint, &request.as_sendable()?, None).await?;
+        let accept = ("Accept", Encoding::CompactJson.accept());
+        let response = self.host.post_with_headers(&endpoint, &[accept], &request.as_sendable()?, None).await?;
+
+        let terms: Vec<String> = serde_json::from_slice(&self.host.expect_success(response)?)?;
+        exact::from_strings(&terms, request.domain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    use clap::Parser;
+
+    use tokio::{net::TcpListener, sync::RwLock};
+
+    use crate::{
+        error::{Error, ErrorType, Result}, 
+        http::{read::{read_http_request, ReadBuffer}, write::{write_chunk, write_chunked_end, write_chunked_response_start, write_http_response}},
+        parse::{
+            encoding::{Encoding, ENCODINGS_HEADER}, remote::Remote, settings::SettingsPonudnik,
+            sequence_provide::{CatalogEntry, CatalogProvider, Domain, Range, Request, SequenceParameter}
+        }
+    };
+    use super::{exact, variants::{formula, function::FunctionSequenceProvider}, Batches, Found, ProviderManager, RemoteSequenceProvider, SequenceInfo, SequenceProvider, Terms, STREAM_BATCH};
+
+    fn manager(arguments: &[&str]) -> ProviderManager {
+        let settings = SettingsPonudnik::parse_from(
+            ["ponudnik", "--centralni-ip", "127.0.0.1", "--centralni-port", "9999"].iter().chain(arguments)
+        );
+        let generator = Remote::new("generator", "127.0.0.1", 9000).unwrap();
+        let mut manager = ProviderManager::new(&settings, &generator, &generator);
+
+        for port in [1, 2] {
+            manager.remote_providers.push(RemoteSequenceProvider {
+                host: Remote::new("remote", "127.0.0.1", port).unwrap(),
+                info: SequenceInfo { name: "remote".to_owned(), description: "".to_owned(), parameters: 0, sequences: 0 },
+                encoding: Encoding::Json
+            });
+        }
+        manager
+    }
+
+    fn info(name: &str) -> SequenceInfo {
+        SequenceInfo { name: name.to_owned(), description: "".to_owned(), parameters: 0, sequences: 0 }
+    }
+
+    #[test]
+    fn test_find_visited() {
+        let manager = manager(&[]);
+        assert!(matches!(manager.find(&info("p_euler"), &["127.0.0.1:1".to_owned()]), Ok(Found::Local(_))));
+        match manager.find(&info("remote"), &["127.0.0.1:1".to_owned()]) {
+            Ok(Found::Remote(candidates)) => assert_eq!(candidates.len(), 1),
+            _ => panic!("Pričakovan oddaljen ponudnik")
+        }
+
+        let visited = ["127.0.0.1:1".to_owned(), "127.0.0.1:2".to_owned()];
+        assert_eq!(manager.find(&info("remote"), &visited).err().unwrap().error_type(), &ErrorType::MissingProvider);
+    }
+
+    #[tokio::test]
+    async fn test_find_ejected() {
+        let manager = RwLock::new(manager(&["--prag-napak", "1"]));
+        let request = Request { range: Range { from: 0, to: 4, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float };
+        assert!(ProviderManager::provide("remote", request.clone(), &manager).await.is_err());
 
-        if (reason, status) == ("OK".to_owned(), 200) {
-            let list: Vec<f64> = serde_json::from_slice(&data)?;
-            Ok(list)
-        } else { Err(Error::remote_invalid_response(&self.host.get_url(), &data)) }
+        // oba oddaljena ponudnika sta izločena, zato ju ne poskusimo več
+        let error = serde_json::to_value(manager.read().await.find(&info("remote"), &[]).err().unwrap()).unwrap();
+        assert_eq!(error["extra"]["ejected"].as_array().unwrap().len(), 2);
+        let error = serde_json::to_value(ProviderManager::provide("remote", request, &manager).await.unwrap_err()).unwrap();
+        assert_eq!(error["extra"].get("attempted"), None);
+        assert!(manager.read().await.health_reports().iter().all(|report| report.failures == 1));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_provide_half_open() {
+        let manager = RwLock::new(manager(&["--prag-napak", "1", "--poskusi", "1", "--cas-izlocitve", "1"]));
+        let request = Request { range: Range { from: 0, to: 4, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float };
+        let failures = |manager: &ProviderManager| manager.health_reports().iter().map(|report| report.failures).collect::<Vec<_>>();
+
+        for _ in 0..2 { assert!(ProviderManager::provide("remote", request.clone(), &manager).await.is_err()); }
+        assert_eq!(failures(&*manager.read().await), vec![1, 1]);
+
+        // izločitev obema poteče, a zaradi --poskusi 1 vsakič poskusimo le enega. Tisti, ki ga ne poskusimo,
+        // ohrani poskusno zahtevo za naslednjič
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        for _ in 0..2 { assert!(ProviderManager::provide("remote", request.clone(), &manager).await.is_err()); }
+        assert_eq!(failures(&*manager.read().await), vec![2, 2]);
+    }
+
+    #[test]
+    fn test_find_hop_limit() {
+        let manager = manager(&["--max-skokov", "1"]);
+        let visited = ["127.0.0.1:3".to_owned()];
+        assert!(manager.find(&info("p_euler"), &visited).is_ok());
+        assert_eq!(manager.find(&info("remote"), &visited).err().unwrap().error_type(), &ErrorType::HopLimitExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_register_local() {
+        let manager = RwLock::new(manager(&[]));
+        let register = |name: &str, formula: &str| {
+            Arc::new(FunctionSequenceProvider::new(Box::new(formula::Sequence::new(name, formula, None, None).unwrap())))
+        };
+
+        manager.write().await.register_local(register("kvadrat", "p0 * n^2")).unwrap();
+        let request = Request {
+            range: Range { from: 0, to: 4, step: 1 },
+            parameters: vec![2.],
+            sequences: vec![],
+            visited: vec![],
+            deadline_ms: None,
+            deadline: None,
+            domain: Domain::Float
+        };
+        assert_eq!(ProviderManager::provide("kvadrat", request, &manager).await, Ok(vec![0., 2., 8., 18.]));
+
+        // enaka signatura, kot jo ima že registrirano ali vgrajeno zaporedje
+        for (name, formula) in [("kvadrat", "p0 + n"), ("arithmetic", "p0 + p1 * n")] {
+            let err = manager.write().await.register_local(register(name, formula)).err().unwrap();
+            assert_eq!(err.error_type(), &ErrorType::AlreadyExists);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provide_composed() {
+        let manager = RwLock::new(manager(&["--vzporedne-zahteve", "2"]));
+        let sequence = |name: &str, parameters: Vec<f64>| SequenceParameter { name: name.to_owned(), parameters, sequences: vec![] };
+        let request = Request {
+            range: Range { from: 0, to: 4, step: 1 },
+            parameters: vec![],
+            sequences: vec![
+                sequence("arithmetic", vec![0., 1.]),
+                sequence("const", vec![10.]),
+                sequence("const", vec![2.]),
+                sequence("geometric", vec![1., 2.])
+            ],
+            visited: vec![],
+            deadline_ms: None,
+            deadline: None,
+            domain: Domain::Float
+        };
+
+        assert_eq!(
+            ProviderManager::provide("lin_com", request, &manager).await,
+            Ok(vec![10., 22., 44., 86.])
+        );
+        assert_eq!(manager.read().await.cache_stats().entries, 5);
+    }
+
+    #[tokio::test]
+    async fn test_provide_exact() {
+        let manager = RwLock::new(manager(&[]));
+        let sequence = |name: &str, parameters: Vec<f64>| SequenceParameter { name: name.to_owned(), parameters, sequences: vec![] };
+        let request = |domain, sequences| Request {
+            range: Range { from: 98, to: 101, step: 1 }, parameters: vec![200.], sequences, visited: vec![], deadline_ms: None, deadline: None, domain
+        };
+
+        let fibonacci = sequence("linear_rec_h", vec![1., 1., 0., 1.]);
+        let terms = ProviderManager::provide_exact("drop", request(Domain::Integer, vec![fibonacci.clone()]), &manager).await.unwrap();
+        assert_eq!(exact::to_strings(&terms), vec![
+            "84885164052257330097714121751630835360966663883732297726369399",
+            "137347080577163115432025771710279131845700275212767467264610201",
+            "222232244629420445529739893461909967206666939096499764990979600"
+        ]);
+        assert_eq!(manager.read().await.cache_stats().entries, 0);
+
+        let halves = SequenceParameter { name: "sum".to_owned(), parameters: vec![], sequences: vec![sequence("const", vec![0.5]), sequence("arithmetic", vec![0., 0.25])] };
+        let terms = ProviderManager::provide_exact("drop", request(Domain::Rational, vec![halves]), &manager).await.unwrap();
+        assert_eq!(exact::to_strings(&terms), vec!["75", "301/4", "151/2"]);
+
+        let error = ProviderManager::provide_exact("drop", request(Domain::Integer, vec![sequence("p_euler", vec![])]), &manager).await.unwrap_err();
+        assert_eq!(error.error_type(), &ErrorType::UnsupportedDomain);
+        let error = ProviderManager::provide_exact("drop", request(Domain::Integer, vec![sequence("const", vec![0.5])]), &manager).await.unwrap_err();
+        assert_eq!(error.error_type(), &ErrorType::GenericParseError);
+    }
+
+    #[tokio::test]
+    async fn test_provide_nonhomogeneous() {
+        let manager = RwLock::new(manager(&[]));
+        let request = |domain| Request {
+            range: Range { from: 3, to: 8, step: 2 },
+            parameters: vec![2., 0.],
+            sequences: vec![SequenceParameter { name: "arithmetic".to_owned(), parameters: vec![0., 1.], sequences: vec![] }],
+            visited: vec![], deadline_ms: None, deadline: None, domain
+        };
+
+        // g pridobimo na 0..8, ne le na range-u zahteve
+        assert_eq!(ProviderManager::provide("linear_rec_nh", request(Domain::Float), &manager).await, Ok(vec![11., 57., 247.]));
+        let terms = ProviderManager::provide_stream("linear_rec_nh", request(Domain::Float), &manager).await.unwrap();
+        assert_eq!(collect(terms).await, Ok(vec![11., 57., 247.]));
+        let terms = ProviderManager::provide_exact("linear_rec_nh", request(Domain::Integer), &manager).await.unwrap();
+        assert_eq!(exact::to_strings(&terms), vec!["11", "57", "247"]);
+    }
+
+    #[tokio::test]
+    async fn test_provide_failover() {
+        let request = Request { range: Range { from: 0, to: 4, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float };
+
+        let all = RwLock::new(manager(&[]));
+        let error = serde_json::to_value(ProviderManager::provide("remote", request.clone(), &all).await.unwrap_err()).unwrap();
+        assert_eq!(error["error"], "RemoteError");
+        assert_eq!(error["extra"]["attempted"].as_array().unwrap().len(), 2);
+        assert_eq!(error["extra"]["last_error"]["error"], "IOError");
+        assert!(all.read().await.health_reports().iter().all(|report| report.failures == 1));
+
+        let limited = RwLock::new(manager(&["--poskusi", "1"]));
+        let error = serde_json::to_value(ProviderManager::provide("remote", request, &limited).await.unwrap_err()).unwrap();
+        assert_eq!(error["extra"]["attempted"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_provide_budget() {
+        let manager = RwLock::new(manager(&["--max-clenov", "100", "--max-cena", "1000"]));
+        let request = |from: u64, to: u64, name: &str, parameters: Vec<f64>| (name.to_owned(), Request {
+            range: Range { from, to, step: 1 }, parameters, sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float
+        });
+
+        let (name, huge) = request(0, u64::MAX, "const", vec![1.]);
+        let error = serde_json::to_value(ProviderManager::provide(&name, huge, &manager).await.unwrap_err()).unwrap();
+        assert_eq!((&error["error"], &error["extra"]["budget"]), (&"BudgetExceeded".into(), &"terms".into()));
+
+        let (name, far) = request(10_000, 10_010, "p_euler", vec![]);
+        let error = serde_json::to_value(ProviderManager::provide(&name, far, &manager).await.unwrap_err()).unwrap();
+        assert_eq!((&error["error"], &error["extra"]["budget"]), (&"BudgetExceeded".into(), &"cost".into()));
+
+        let (name, small) = request(0, 3, "p_euler", vec![]);
+        assert_eq!(ProviderManager::provide(&name, small, &manager).await, Ok(vec![1., 2., 3.]));
+    }
+
+    #[tokio::test]
+    async fn test_provide_exact_budget() {
+        let manager = RwLock::new(manager(&["--max-cena", "10000"]));
+        let request = |from: u64, name: &str, parameters: Vec<f64>, domain| (name.to_owned(), Request {
+            range: Range { from, to: from + 1, step: 1 }, parameters, sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain
+        });
+        let budget = |error: Error| serde_json::to_value(error).unwrap()["extra"]["budget"].clone();
+
+        // členi bi imeli preveč bitov, zato jih sploh ne računamo
+        let (name, fibonacci) = request(100_000_000_000, "linear_rec_h", vec![1., 1., 0., 1.], Domain::Integer);
+        assert_eq!(budget(ProviderManager::provide_exact(&name, fibonacci, &manager).await.unwrap_err()), "bits");
+        let (name, powers) = request(100_000_000, "geometric", vec![1., 2.], Domain::Integer);
+        assert_eq!(budget(ProviderManager::provide_exact(&name, powers, &manager).await.unwrap_err()), "bits");
+        let (name, halves) = request(100_000_000, "geometric", vec![1., 0.5], Domain::Rational);
+        assert_eq!(budget(ProviderManager::provide_exact(&name, halves, &manager).await.unwrap_err()), "bits");
+
+        // v plavajoči vejici je izračun poceni, točno pa vsaka operacija stane sorazmerno velikosti členov
+        let (name, fibonacci) = request(100_000, "linear_rec_h", vec![1., 1., 0., 1.], Domain::Float);
+        assert!(ProviderManager::provide(&name, fibonacci, &manager).await.is_ok());
+        let (name, fibonacci) = request(100_000, "linear_rec_h", vec![1., 1., 0., 1.], Domain::Integer);
+        assert_eq!(budget(ProviderManager::provide_exact(&name, fibonacci, &manager).await.unwrap_err()), "cost");
+
+        let (name, fibonacci) = request(100, "linear_rec_h", vec![1., 1., 0., 1.], Domain::Integer);
+        let terms = ProviderManager::provide_exact(&name, fibonacci, &manager).await.unwrap();
+        assert_eq!(exact::to_strings(&terms), vec!["354224848179261915075"]);
+    }
+
+    // zaporedje, katerega računanje traja dlje od roka
+    struct Slow;
+    impl SequenceProvider for Slow {
+        fn get_info(&self) -> SequenceInfo { info("slow") }
+        fn generate(&self, range: Range, _: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64>> {
+            std::thread::sleep(std::time::Duration::from_secs(2));
+            Ok(vec![0.; range.len() as usize])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provide_deadline() {
+        let manager = RwLock::new(manager(&["--rok", "1"]));
+        manager.write().await.register_local(Arc::new(Slow)).unwrap();
+        let request = Request { range: Range { from: 0, to: 4, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float };
+
+        // računanje ne blokira izvajalnika, zato rok poteče pravočasno
+        let start = std::time::Instant::now();
+        let error = ProviderManager::provide("slow", request.clone(), &manager).await.unwrap_err();
+        assert_eq!(error.error_type(), &ErrorType::Timeout);
+        assert!(start.elapsed() < std::time::Duration::from_millis(1500));
+
+        let request = Request { deadline_ms: Some(100), ..request };
+        let start = std::time::Instant::now();
+        assert!(ProviderManager::provide_stream("slow", request, &manager).await.is_err());
+        assert!(start.elapsed() < std::time::Duration::from_millis(600));
+    }
+
+    // prebere vse kose pretakanega zaporedja
+    async fn collect(mut batches: Batches) -> Result<Vec<f64>> {
+        let mut terms = vec![];
+        while let Some(batch) = batches.recv().await { terms.extend(batch?); }
+        Ok(terms)
+    }
+
+    // zaporedje, ki člene računa počasi in šteje, koliko jih je izračunalo
+    struct SlowTerms(Arc<AtomicUsize>);
+    impl SequenceProvider for SlowTerms {
+        fn get_info(&self) -> SequenceInfo { info("slow_terms") }
+        fn generate(&self, _: Range, _: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64>> { unreachable!() }
+        fn generate_iter(self: Arc<Self>, range: Range, _: Vec<f64>, _: Vec<Vec<f64>>) -> Result<Terms> {
+            Ok(Box::new((range.from..range.to).map(move |n| {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(n as f64)
+            })))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provide_stream_deadline() {
+        let computed = Arc::new(AtomicUsize::new(0));
+        let manager = RwLock::new(manager(&[]));
+        manager.write().await.register_local(Arc::new(SlowTerms(computed.clone()))).unwrap();
+        let request = |deadline_ms| Request {
+            range: Range { from: 0, to: 1_000_000, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms, deadline: None, domain: Domain::Float
+        };
+
+        // rok velja tudi med pretakanjem, člene pa računamo izven izvajalnika
+        let start = std::time::Instant::now();
+        let error = collect(ProviderManager::provide_stream("slow_terms", request(Some(300)), &manager).await.unwrap()).await.unwrap_err();
+        assert_eq!(error.error_type(), &ErrorType::Timeout);
+        assert!(start.elapsed() < std::time::Duration::from_millis(1000));
+
+        // ko prejemnik kanal zapre, se računanje ustavi
+        let mut batches = ProviderManager::provide_stream("slow_terms", request(None), &manager).await.unwrap();
+        assert_eq!(batches.recv().await.unwrap().unwrap().len(), STREAM_BATCH);
+        drop(batches);
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let stopped = computed.load(Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert_eq!(computed.load(Ordering::SeqCst), stopped);
+    }
+
+    #[tokio::test]
+    async fn test_provide_stream() {
+        let manager = RwLock::new(manager(&[]));
+        let request = Request {
+            range: Range { from: 2, to: 12, step: 3 },
+            parameters: vec![1., 1., 0., 1.],
+            sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float
+        };
+
+        let terms = ProviderManager::provide_stream("linear_rec_h", request.clone(), &manager).await.unwrap();
+        assert_eq!(collect(terms).await, Ok(vec![1., 5., 21., 89.]));
+        assert_eq!(manager.read().await.cache_stats().entries, 0);
+        assert_eq!(ProviderManager::provide("linear_rec_h", request, &manager).await, Ok(vec![1., 5., 21., 89.]));
+
+        // kombinatorji, ki povozijo provide, morajo delovati tudi pri pretakanju
+        let fibonacci = SequenceParameter { name: "linear_rec_h".to_owned(), parameters: vec![1., 1., 0., 1.], sequences: vec![] };
+        let request = Request {
+            range: Range { from: 0, to: 20_000, step: 1 },
+            parameters: vec![3.],
+            sequences: vec![SequenceParameter { name: "drop".to_owned(), parameters: vec![2.], sequences: vec![fibonacci] }],
+            visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float
+        };
+        let terms = collect(ProviderManager::provide_stream("drop", request.clone(), &manager).await.unwrap()).await.unwrap();
+        assert_eq!((terms.len(), &terms[..4]), (20_000, [5., 8., 13., 21.].as_slice()));
+        assert_eq!(ProviderManager::provide("drop", request, &manager).await.unwrap()[..4], terms[..4]);
+    }
+
+    #[tokio::test]
+    async fn test_provide_remote_encoding() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = Remote::new("remote", "127.0.0.1", listener.local_addr().unwrap().port()).unwrap();
+
+        // remote oglašuje NDJSON in CSV, nato pa zaporedje pošlje po kosih v zahtevanem zapisu
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = ReadBuffer::default();
+            while let Some(request) = read_http_request(&mut stream, &mut buffer).await.unwrap() {
+                if request.path == "/sequence/" {
+                    let list = serde_json::to_vec(&[info("remote")]).unwrap();
+                    let headers = [(ENCODINGS_HEADER, "application/x-ndjson, text/csv".to_owned())];
+                    write_http_response("200 OK", "application/json", &headers, &list, true, &mut stream).await.unwrap();
+                } else {
+                    let encoding = Encoding::negotiate(request.header("accept")).unwrap();
+                    write_chunked_response_start("200 OK", encoding.content_type(), &[], &mut stream).await.unwrap();
+                    write_chunk(&encoding.encode(&[1., 2.5]).unwrap(), &mut stream).await.unwrap();
+                    write_chunk(&encoding.encode(&[-3.]).unwrap(), &mut stream).await.unwrap();
+                    write_chunked_end(&mut stream).await.unwrap();
+                }
+            }
+        });
+
+        let providers = ProviderManager::get_remote_sequence_providers(&remote).await.unwrap();
+        assert_eq!(providers[0].encoding, Encoding::Csv);
+
+        let mut manager = manager(&[]);
+        manager.remote_providers = providers;
+        let request = Request { range: Range { from: 0, to: 3, step: 1 }, parameters: vec![], sequences: vec![], visited: vec![], deadline_ms: None, deadline: None, domain: Domain::Float };
+        assert_eq!(ProviderManager::provide("remote", request, &RwLock::new(manager)).await, Ok(vec![1., 2.5, -3.]));
+    }
+
+    #[tokio::test]
+    async fn test_update_providers_catalog() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let central = Remote::new("central", "127.0.0.1", listener.local_addr().unwrap().port()).unwrap();
+        let generator = Remote::new("generator", "127.0.0.1", 9000).unwrap();
+        let remote = Remote::new("remote", "127.0.0.1", 1).unwrap();
+
+        // katalog vsebuje tudi nas, saj smo registrirani
+        let catalog = vec![CatalogEntry {
+            info: info("remote"),
+            providers: vec![
+                CatalogProvider { remote: generator.clone(), encodings: None },
+                CatalogProvider { remote: remote.clone(), encodings: Some("text/csv".to_owned()) }
+            ]
+        }];
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = ReadBuffer::default();
+            while let Some(request) = read_http_request(&mut stream, &mut buffer).await.unwrap() {
+                assert_eq!(request.path, "/sequences");
+                write_http_response("200 OK", "application/json", &[], &serde_json::to_vec(&catalog).unwrap(), true, &mut stream).await.unwrap();
+            }
+        });
+
+        let settings = SettingsPonudnik::parse_from(["ponudnik", "--centralni-ip", "127.0.0.1", "--centralni-port", "9999"]);
+        let manager = RwLock::new(ProviderManager::new(&settings, &generator, &central));
+        ProviderManager::update_providers("/project", &manager).await.unwrap();
+
+        let manager = manager.read().await;
+        assert_eq!(manager.remote_providers.len(), 1);
+        assert_eq!((&manager.remote_providers[0].host, manager.remote_providers[0].encoding), (&remote, Encoding::Csv));
+    }
+}
@@ -0,0 +1,157 @@
+//! Računanje po modulu M < 2^64. Produkte računamo v `u128`, da ne pride do prekoračitve,
+//! Eulerjevo funkcijo pa izračunamo iz razcepa M (Miller-Rabin in Pollardova rho metoda).
+
+use num_integer::Integer;
+
+use super::recurrence::{Field, Ring};
+
+// z njimi je Miller-Rabin deterministično pravilen za vsa števila pod 2^64
+const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+/// base^exp mod m s kvadriranjem in množenjem
+pub fn pow_mod(base: u64, mut exp: u128, m: u64) -> u64 {
+    let mut base = base % m;
+    let mut result = 1 % m;
+    while exp > 0 {
+        if exp & 1 == 1 { result = mul_mod(result, base, m); }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 { return false; }
+    for p in WITNESSES {
+        if n.is_multiple_of(p) { return n == p; }
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    WITNESSES.iter().all(|&a| {
+        let mut x = pow_mod(a, d as u128, n);
+        if x == 1 || x == n - 1 { return true; }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 { return true; }
+        }
+        false
+    })
+}
+
+// netrivialen delitelj sestavljenega lihega n (Pollardova rho metoda s Floydovim iskanjem cikla)
+fn pollard_rho(n: u64) -> u64 {
+    for c in 1..n {
+        let next = |x: u64| ((x as u128 * x as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = next(x);
+            y = next(next(y));
+            d = x.abs_diff(y).gcd(&n);
+        }
+        if d != n { return d; }
+    }
+    n
+}
+
+/// Praštevilski delitelji n (s ponovitvami), urejeni po velikosti
+pub fn factor(n: u64) -> Vec<u64> {
+    let mut factors = vec![];
+    let mut n = n;
+    for p in WITNESSES {
+        while n > 1 && n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+    }
+
+    let mut stack = vec![n];
+    while let Some(n) = stack.pop() {
+        if n == 1 { continue; }
+        if is_prime(n) { factors.push(n); continue; }
+        let d = pollard_rho(n);
+        stack.extend([d, n / d]);
+    }
+    factors.sort_unstable();
+    factors
+}
+
+/// Eulerjeva funkcija φ(n)
+pub fn totient(n: u64) -> u64 {
+    let mut factors = factor(n);
+    factors.dedup();
+    factors.iter().fold(n, |result, p| result / p * (p - 1))
+}
+
+/// Ostanek po modulu, s katerim lahko računamo v `recurrence::Kitamasa`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Residue {
+    value:      u64,
+    modulus:    u64
+}
+
+impl Residue {
+    pub fn new(value: impl Into<i128>, modulus: u64) -> Self {
+        Residue { value: value.into().rem_euclid(modulus as i128) as u64, modulus }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+}
+
+impl Ring for Residue {
+    fn add(&self, other: &Self) -> Self {
+        Residue { value: ((self.value as u128 + other.value as u128) % self.modulus as u128) as u64, modulus: self.modulus }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Residue { value: mul_mod(self.value, other.value, self.modulus), modulus: self.modulus }
+    }
+}
+
+// deljenje z Malim Fermatovim izrekom, zato mora biti modul praštevilo
+impl Field for Residue {
+    fn sub(&self, other: &Self) -> Self {
+        Residue { value: ((self.value as u128 + self.modulus as u128 - other.value as u128) % self.modulus as u128) as u64, modulus: self.modulus }
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        self.mul(&Residue { value: pow_mod(other.value, (self.modulus - 2) as u128, self.modulus), modulus: self.modulus })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{factor, is_prime, pow_mod, totient};
+
+    #[test]
+    fn test_modular() {
+        assert_eq!(pow_mod(3, 200, 1_000_000_007), 136_318_165);
+        assert_eq!(pow_mod(u64::MAX - 1, u128::MAX, u64::MAX), u64::MAX - 1);
+        assert_eq!(pow_mod(5, 0, 1), 0);
+
+        let primes = [2, 3, 37, 1_000_000_007, (1 << 61) - 1, 18_446_744_073_709_551_557];
+        let composite = [0, 1, 561, 3_215_031_751, 1_000_000_007 * 998_244_353, u64::MAX];
+        assert!(primes.iter().all(|&p| is_prime(p)));
+        assert!(composite.iter().all(|&n| !is_prime(n)));
+
+        assert!(factor(1).is_empty());
+        assert_eq!(factor(1 << 40), vec![2; 40]);
+        assert_eq!(factor(u64::MAX), vec![3, 5, 17, 257, 641, 65537, 6_700_417]);
+        assert_eq!(factor(4_294_967_291 * 4_294_967_279), vec![4_294_967_279, 4_294_967_291]);
+
+        assert_eq!(totient(1), 1);
+        assert_eq!(totient(36), 12);
+        assert_eq!(totient(1_000_000_007), 1_000_000_006);
+        assert_eq!(totient(4_294_967_291 * 4_294_967_279), 4_294_967_290 * 4_294_967_278);
+    }
+}
@@ -0,0 +1,399 @@
+//! Kitamasova metoda za člene homogene linearne rekurzije f(n) = a_1 f(n-1) + ... + a_k f(n-k) v poljubnem kolobarju.
+//! Polinom x^n izrazimo po modulu karakterističnega polinoma x^k - a_1 x^(k-1) - ... - a_k, tako da je f(n)
+//! linearna kombinacija začetnih členov. Prvi člen stane O(k^2 log n) operacij, vsak naslednji pa O(k^2).
+//! Rekurzije, pri katerih to ni mogoče (nehomogene, s polinomskimi koeficienti), računamo člen po člen z `iterate`.
+//! Najkrajšo rekurzijo, ki ji zadoščajo podani členi, poiščemo z algoritmom Berlekamp-Massey.
+
+use std::collections::VecDeque;
+
+use num_integer::Integer;
+use num_traits::ToPrimitive;
+
+use crate::{
+    error::{Error, Result},
+    parse::sequence_provide::{Domain, Range, RecurrenceQuery, SequenceParameter},
+    sequnce_providers::{exact::{self, Exact}, modular::{self, Residue}}
+};
+
+/// Kolobar, v katerem računamo člene rekurzije
+pub trait Ring: Clone {
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+}
+
+impl Ring for f64 {
+    fn add(&self, other: &Self) -> Self { self + other }
+    fn mul(&self, other: &Self) -> Self { self * other }
+}
+
+impl Ring for Exact {
+    fn add(&self, other: &Self) -> Self { self + other }
+    fn mul(&self, other: &Self) -> Self { self * other }
+}
+
+/// Obseg, v katerem lahko rekurzijo poiščemo z `berlekamp_massey`
+pub trait Field: Ring {
+    fn sub(&self, other: &Self) -> Self;
+    fn div(&self, other: &Self) -> Self;
+    fn is_zero(&self) -> bool;
+}
+
+impl Field for Exact {
+    fn sub(&self, other: &Self) -> Self { self - other }
+    fn div(&self, other: &Self) -> Self { self / other }
+    fn is_zero(&self) -> bool { num_traits::Zero::is_zero(self) }
+}
+
+pub struct Kitamasa<T> {
+    coefficients:   Vec<T>,
+    zero:           T,
+    one:            T
+}
+
+impl<T: Ring> Kitamasa<T> {
+    /// Rekurzija s koeficienti [a_1, ..., a_k]. Ker ničle in enice v kolobarju (npr. ostanki po modulu) ne poznamo
+    /// vnaprej, ju podamo.
+    pub fn new(coefficients: Vec<T>, zero: T, one: T) -> Self {
+        Kitamasa { coefficients, zero, one }
+    }
+
+    // polinom (koeficienti od x^0 naprej) zmanjšamo z x^k = a_1 x^(k-1) + ... + a_k
+    fn reduce(&self, mut poly: Vec<T>) -> Vec<T> {
+        let k = self.coefficients.len();
+        for d in (k..poly.len()).rev() {
+            let top = poly[d].clone();
+            for (i, a) in self.coefficients.iter().enumerate() {
+                poly[d-1-i] = poly[d-1-i].add(&top.mul(a));
+            }
+        }
+        poly.resize(k, self.zero.clone());
+        poly
+    }
+
+    fn multiply(&self, p: &[T], q: &[T]) -> Vec<T> {
+        let mut product = vec![self.zero.clone(); p.len() + q.len() - 1];
+        for (i, a) in p.iter().enumerate() {
+            for (j, b) in q.iter().enumerate() {
+                product[i+j] = product[i+j].add(&a.mul(b));
+            }
+        }
+        self.reduce(product)
+    }
+
+    /// x^n po modulu karakterističnega polinoma
+    fn power(&self, mut n: u64) -> Vec<T> {
+        let mut result = self.reduce(vec![self.one.clone()]);
+        let mut base = self.reduce(vec![self.zero.clone(), self.one.clone()]);
+        while n > 0 {
+            if n & 1 == 1 { result = self.multiply(&result, &base); }
+            base = self.multiply(&base, &base);
+            n >>= 1;
+        }
+        result
+    }
+
+    /// Členi f(from), f(from + step), ... z začetnimi pogoji [f(0), ..., f(k-1)]
+    pub fn terms(&self, range: Range, initial: &[T]) -> Vec<T> {
+        let mut poly = self.power(range.from);
+        let step = self.power(range.step);
+
+        (0..range.len()).map(|_| {
+            let term = poly.iter().zip(initial).fold(self.zero.clone(), |sum, (p, f)| sum.add(&p.mul(f)));
+            poly = self.multiply(&poly, &step);
+            term
+        }).collect()
+    }
+}
+
+impl Kitamasa<Exact> {
+    pub fn exact(coefficients: &[Exact]) -> Self {
+        Kitamasa::new(coefficients.to_vec(), exact::zero(), exact::from_integer(1))
+    }
+}
+
+/// Členi f(from), f(from + step), ... rekurzije, ki jo računamo člen po člen od začetnih pogojev [f(0), ..., f(k-1)] naprej.
+/// `next` iz indeksa n in prejšnjih členov [f(n-k), ..., f(n-1)] izračuna f(n).
+pub fn iterate<T: Clone>(initial: &[T], range: Range, mut next: impl FnMut(u64, &[T]) -> T) -> Vec<T> {
+    let mut previous: VecDeque<T> = initial.iter().cloned().collect();
+    let mut result = vec![];
+    for n in 0..range.to {
+        let term = match initial.get(n as usize) {
+            Some(term) => term.clone(),
+            None => {
+                let term = next(n, previous.make_contiguous());
+                previous.pop_front();
+                previous.push_back(term.clone());
+                term
+            }
+        };
+        if n >= range.from && (n - range.from).is_multiple_of(range.step) { result.push(term); }
+    }
+    result
+}
+
+/// Oceni število bitov točnih členov do f(n) rekurzije, katere členi na vsak korak po absolutni vrednosti narastejo
+/// največ za faktor `factor`, imenovalci pa največ za produkt imenovalcev koeficientov `coefficients`
+pub fn estimate_bits(coefficients: &[f64], factor: f64, initial: &[f64], n: u64) -> u64 {
+    let denominators: f64 = coefficients.iter().map(|a| exact::denominator_bits(*a)).sum();
+    // števec je največ absolutna vrednost, pomnožena z imenovalcem
+    let step = factor.max(1.).log2() + 2. * denominators;
+    let initial = initial.iter().map(|f| exact::bits(*f)).fold(0., f64::max);
+    (step * n as f64 + initial).ceil() as u64
+}
+
+/// Koeficienti [a_1, ..., a_L] najkrajše linearne rekurzije, ki ji zadoščajo vsi členi (Berlekamp-Massey, O(n^2)).
+/// Rekurzija je enolično določena, če je členov vsaj 2L.
+pub fn berlekamp_massey<T: Field>(terms: &[T], zero: T, one: T) -> Vec<T> {
+    // povezovalni polinom C(x) = 1 + c_1 x + ... + c_L x^L, tako da je f(n) + c_1 f(n-1) + ... + c_L f(n-L) = 0
+    let mut connection = vec![one.clone()];
+    // C pred zadnjo spremembo dolžine L, z neujemanjem, ki jo je povzročilo
+    let (mut previous, mut previous_discrepancy) = (vec![one.clone()], one);
+    let (mut length, mut shift) = (0, 1);
+
+    for (n, term) in terms.iter().enumerate() {
+        let discrepancy = (1..=length).fold(term.clone(), |sum, i| sum.add(&connection[i].mul(&terms[n-i])));
+        if discrepancy.is_zero() {
+            shift += 1;
+            continue;
+        }
+
+        // C(x) -= d / d' x^shift B(x)
+        let factor = discrepancy.div(&previous_discrepancy);
+        let old = connection.clone();
+        if connection.len() < previous.len() + shift { connection.resize(previous.len() + shift, zero.clone()); }
+        for (i, b) in previous.iter().enumerate() {
+            connection[i+shift] = connection[i+shift].sub(&factor.mul(b));
+        }
+
+        if 2*length <= n {
+            length = n + 1 - length;
+            previous = old;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else { shift += 1; }
+    }
+
+    connection.resize(length + 1, zero.clone());
+    connection[1..].iter().map(|c| zero.sub(c)).collect()
+}
+
+/// Največji modul, pri katerem so vsi ostanki točno predstavljivi kot `f64`
+pub const MAX_EXACT_MODULUS: u64 = 1 << 53;
+
+/// Omejitve iskanja rekurzije, enake kot pri računanju zaporedij
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisLimits {
+    /// Največja stopnja rekurzije, saj daljših zaporedij `linear_rec_h` ne ponujamo
+    pub max_degree: usize,
+    /// Največje število bitov koeficientov med računanjem
+    pub max_bits:   u64,
+    /// Največja ocenjena cena računanja
+    pub max_cost:   u64
+}
+
+impl AnalysisLimits {
+    // Berlekamp-Massey izvede O(n^2) operacij. Koeficienti nad racionalnimi števili med računanjem narastejo do velikosti
+    // vseh členov skupaj, množenje in krajšanje ulomkov te velikosti pa stane približno kvadrat števila 64-bitnih besed.
+    fn check(&self, terms: usize, bits: u64) -> Result<()> {
+        if bits > self.max_bits { return Err(Error::analysis_budget_exceeded("bits", bits, self.max_bits)); }
+        let words = bits.div_ceil(64).max(1);
+        let cost = (terms as u64).pow(2).saturating_mul(words.saturating_mul(words));
+        if cost > self.max_cost { Err(Error::analysis_budget_exceeded("cost", cost, self.max_cost)) } else { Ok(()) }
+    }
+}
+
+/// Poišče najkrajšo linearno rekurzijo podanih členov in jo vrne kot zaporedje `linear_rec_h` s koeficienti in začetnimi
+/// pogoji (oziroma `linear_rec_h_mod`, če iščemo po modulu), ki ga lahko neposredno uporabimo v zahtevi
+pub fn find_linear_recurrence(query: &RecurrenceQuery, limits: &AnalysisLimits) -> Result<SequenceParameter> {
+    let (name, mut coefficients, initial) = match query.modulus {
+        None => {
+            let terms: Vec<Exact> = query.terms.iter().map(|term| exact::from_term(term, Domain::Rational)).collect::<Result<_>>()?;
+            limits.check(terms.len(), terms.iter().map(exact::size_bits).sum())?;
+            let coefficients = berlekamp_massey(&terms, exact::zero(), exact::from_integer(1));
+            check_length(terms.len(), coefficients.len(), limits.max_degree)?;
+
+            // parametre pošljemo kot f64, zato rekurzije, ki je ne moremo zapisati točno, ne vrnemo
+            let to_f64 = |values: &[Exact]| values.iter().map(exact::to_f64_exact).collect::<Result<Vec<f64>>>();
+            ("linear_rec_h", to_f64(&coefficients)?, to_f64(&terms[..coefficients.len()])?)
+        },
+        Some(modulus) => {
+            // ostanki so manjši od modula, zato so do 2^53 v f64 točni
+            if modulus > MAX_EXACT_MODULUS || !modular::is_prime(modulus) { return Err(Error::invalid_modulus(modulus, MAX_EXACT_MODULUS)); }
+            let terms: Vec<Residue> = query.terms.iter().map(|term| {
+                let value = exact::from_term(term, Domain::Integer)?.to_integer().mod_floor(&modulus.into());
+                Ok(Residue::new(value.to_u64().unwrap_or_default(), modulus))
+            }).collect::<Result<_>>()?;
+            // ostanki imajo stalno velikost
+            limits.check(terms.len(), 0)?;
+            let coefficients = berlekamp_massey(&terms, Residue::new(0, modulus), Residue::new(1, modulus));
+            check_length(terms.len(), coefficients.len(), limits.max_degree)?;
+
+            let to_f64 = |values: &[Residue]| values.iter().map(|value| value.value() as f64).collect::<Vec<f64>>();
+            ("linear_rec_h_mod", to_f64(&coefficients), to_f64(&terms[..coefficients.len()]))
+        }
+    };
+
+    // ničelnemu zaporedju ustreza rekurzija f(n) = 0 f(n-1), f(0) = 0, saj linear_rec_h nima stopnje 0
+    let mut initial = initial;
+    if coefficients.is_empty() {
+        coefficients.push(0.);
+        initial.push(0.);
+    }
+
+    let mut parameters = [coefficients, initial].concat();
+    parameters.extend(query.modulus.map(|modulus| modulus as f64));
+    Ok(SequenceParameter { name: name.to_owned(), parameters, sequences: vec![] })
+}
+
+// rekurzija stopnje L je iz členov enolično določena le, če jih je vsaj 2L, vrnemo pa jo le, če jo lahko ponudimo
+fn check_length(terms: usize, degree: usize, max_degree: usize) -> Result<()> {
+    if 2 * degree > terms { Err(Error::recurrence_not_found(terms, degree)) }
+    else if degree > max_degree { Err(Error::recurrence_too_deep(degree, max_degree)) }
+    else { Ok(()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Result;
+    use crate::parse::sequence_provide::{Domain, Range, RecurrenceQuery, Term};
+    use crate::sequnce_providers::{exact, implementations::{linear_recursion_h, linear_recursion_h_mod}, modular::Residue, SequenceProvider};
+    use super::{berlekamp_massey, find_linear_recurrence, iterate, AnalysisLimits, Kitamasa};
+
+    const LIMITS: AnalysisLimits = AnalysisLimits { max_degree: 4, max_bits: 1 << 22, max_cost: 100_000_000 };
+
+    #[test]
+    fn test_kitamasa() {
+        let fibonacci = Kitamasa::exact(&[exact::from_integer(1), exact::from_integer(1)]);
+        let initial = [exact::from_integer(0), exact::from_integer(1)];
+        let terms = fibonacci.terms(Range { from: 0, to: 10, step: 1 }, &initial);
+        assert_eq!(exact::to_strings(&terms), vec!["0", "1", "1", "2", "3", "5", "8", "13", "21", "34"]);
+        assert_eq!(exact::to_strings(&fibonacci.terms(Range { from: 300, to: 301, step: 1 }, &initial)), vec!["222232244629420445529739893461909967206666939096499764990979600"]);
+
+        let residue = |value: i64| Residue::new(value, 1_000_000_007);
+        let fibonacci = Kitamasa::new(vec![residue(1), residue(1)], residue(0), residue(1));
+        let terms = fibonacci.terms(Range { from: 1_000_000_000_000_000, to: 1_000_000_000_000_003, step: 1 }, &[residue(0), residue(1)]);
+        assert_eq!(terms.iter().map(Residue::value).collect::<Vec<_>>(), vec![648_325_137, 133_295_545, 781_620_682]);
+
+        // f(n) = 2 f(n-1) - f(n-2) + 3 f(n-3)
+        let residue = |value: i64| Residue::new(value, 998_244_353);
+        let recurrence = Kitamasa::new(vec![residue(2), residue(-1), residue(3)], residue(0), residue(1));
+        let terms = recurrence.terms(Range { from: 1_000_000_000_000, to: 1_000_000_000_021, step: 7 }, &[residue(1), residue(-2), residue(5)]);
+        assert_eq!(terms.iter().map(Residue::value).collect::<Vec<_>>(), vec![412_135_033, 773_274_671, 310_730_254]);
+
+        // rekurzija stopnje 1 je geometrijsko zaporedje
+        let geometric = Kitamasa::exact(&[exact::from_integer(3)]);
+        assert_eq!(exact::to_strings(&geometric.terms(Range { from: 2, to: 8, step: 2 }, &[exact::from_integer(2)])), vec!["18", "162", "1458"]);
+    }
+
+    #[test]
+    fn test_iterate() {
+        let fibonacci = |range| iterate(&[0, 1], range, |_, previous| previous[0] + previous[1]);
+        assert_eq!(fibonacci(Range { from: 0, to: 10, step: 1 }), vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+        assert_eq!(fibonacci(Range { from: 1, to: 10, step: 4 }), vec![1, 5, 34]);
+        assert!(fibonacci(Range { from: 0, to: 0, step: 1 }).is_empty());
+
+        let factorial = iterate(&[1u64], Range { from: 3, to: 6, step: 1 }, |n, previous| n * previous[0]);
+        assert_eq!(factorial, vec![6, 24, 120]);
+    }
+
+    #[test]
+    fn test_berlekamp_massey() {
+        let integers = |values: &[i64]| values.iter().map(|value| exact::from_integer(*value)).collect::<Vec<_>>();
+        let find = |values: &[i64]| exact::to_strings(&berlekamp_massey(&integers(values), exact::zero(), exact::from_integer(1)));
+        assert_eq!(find(&[0, 1, 1, 2, 3, 5, 8, 13]), vec!["1", "1"]);
+        assert_eq!(find(&[1, 3, 9, 27, 81]), vec!["3"]);
+        assert_eq!(find(&[1, -2, 5, 15, 19, 38, 102, 223]), vec!["2", "-1", "3"]);
+        assert_eq!(find(&[0, 1, 4, 9, 16, 25, 36]), vec!["3", "-3", "1"]);
+        assert!(find(&[0, 0, 0, 0]).is_empty());
+
+        let residue = |value: i64| Residue::new(value, 998_244_353);
+        let terms = [1, -2, 5, 15, 19, 38, 102, 223].map(residue);
+        let coefficients = berlekamp_massey(&terms, residue(0), residue(1));
+        assert_eq!(coefficients.iter().map(Residue::value).collect::<Vec<_>>(), vec![2, 998_244_352, 3]);
+    }
+
+    #[test]
+    fn test_find_linear_recurrence() {
+        let query = |terms: Vec<Term>, modulus| RecurrenceQuery { terms, modulus };
+        let numbers = |values: &[f64]| values.iter().map(|value| Term::Number(*value)).collect::<Vec<_>>();
+
+        // Fibonaccijeva števila, podana kot nizi
+        let fibonacci = exact::to_strings(&Kitamasa::exact(&[exact::from_integer(1), exact::from_integer(1)])
+            .terms(Range { from: 50, to: 60, step: 1 }, &[exact::from_integer(0), exact::from_integer(1)]));
+        let sequence = find_linear_recurrence(&query(fibonacci.into_iter().map(Term::Exact).collect(), None), &LIMITS).unwrap();
+        assert_eq!(sequence.name, "linear_rec_h");
+        assert_eq!(sequence.parameters, vec![1., 1., 12586269025., 20365011074.]);
+
+        let sequence = find_linear_recurrence(&query(numbers(&[1., 0.5, 0.25, 0.125]), None), &LIMITS).unwrap();
+        assert_eq!((sequence.name.as_str(), sequence.parameters), ("linear_rec_h", vec![0.5, 1.]));
+
+        let sequence = find_linear_recurrence(&query(numbers(&[0., 0., 0.]), None), &LIMITS).unwrap();
+        assert_eq!(sequence.parameters, vec![0., 0.]);
+
+        let sequence = find_linear_recurrence(&query(numbers(&[0., 1., 1., 2., 3., 5., 1., 6.]), Some(7)), &LIMITS).unwrap();
+        assert_eq!((sequence.name.as_str(), sequence.parameters), ("linear_rec_h_mod", vec![1., 1., 0., 1., 7.]));
+
+        // premalo členov za enolično rekurzijo, nepraštevilski modul in neceli členi po modulu
+        assert!(find_linear_recurrence(&query(numbers(&[1., 2., 5.]), None), &LIMITS).is_err());
+        assert!(find_linear_recurrence(&query(numbers(&[0., 1., 1., 2.]), Some(10)), &LIMITS).is_err());
+        assert!(find_linear_recurrence(&query(numbers(&[0.5, 1., 1., 2.]), Some(7)), &LIMITS).is_err());
+        assert!(find_linear_recurrence(&query(vec![Term::Exact("1/0".to_owned())], None), &LIMITS).is_err());
+
+        // parametrov, ki jih v f64 ne moremo zapisati točno, ne zaokrožimo
+        let fibonacci = exact::to_strings(&Kitamasa::exact(&[exact::from_integer(1), exact::from_integer(1)])
+            .terms(Range { from: 200, to: 210, step: 1 }, &[exact::from_integer(0), exact::from_integer(1)]));
+        assert!(find_linear_recurrence(&query(fibonacci.into_iter().map(Term::Exact).collect(), None), &LIMITS).is_err());
+        assert!(find_linear_recurrence(&query(numbers(&[0., 1., 1., 2.]), Some((1 << 61) - 1)), &LIMITS).is_err());
+    }
+
+    #[test]
+    fn test_find_linear_recurrence_round_trip() {
+        let range = |to: usize| Range { from: 0, to: to as u64, step: 1 };
+
+        // najdeno zaporedje linear_rec_h mora vrniti podane člene
+        let sequences: [&[&str]; 3] = [
+            &["1", "-2", "5", "15", "19", "38", "102", "223"],
+            &["3", "1/2", "1", "5/8", "9/16", "7/16", "23/64", "37/128"],
+            &["9007199254740881", "1", "9007199254740882", "2", "9007199254740883", "3"]
+        ];
+        for terms in sequences {
+            let query = RecurrenceQuery { terms: terms.iter().map(|term| Term::Exact(term.to_string())).collect(), modulus: None };
+            let sequence = find_linear_recurrence(&query, &LIMITS).unwrap();
+            let parameters = sequence.parameters.iter().map(|parameter| exact::from_f64(*parameter, Domain::Rational)).collect::<Result<Vec<_>>>().unwrap();
+            let provider = linear_recursion_h::Provider::new(parameters.len() / 2);
+            let generated = provider.generate_exact(range(terms.len()), Domain::Rational, &parameters, &[]).unwrap();
+            assert_eq!(exact::to_strings(&generated), terms);
+        }
+
+        // po največjem praštevilu, manjšem od 2^53, so ostanki še točni
+        let modulus = 9_007_199_254_740_881u64;
+        let terms = linear_recursion_h_mod::Provider::new(3)
+            .generate(range(12), &[3., 9_007_199_254_740_000., 7., 123_456_789_012_345., 2., 1., modulus as f64], &[]).unwrap();
+        let query = RecurrenceQuery { terms: terms.iter().map(|term| Term::Number(*term)).collect(), modulus: Some(modulus) };
+        let sequence = find_linear_recurrence(&query, &LIMITS).unwrap();
+        assert_eq!(sequence.parameters.last(), Some(&(modulus as f64)));
+        let provider = linear_recursion_h_mod::Provider::new(sequence.parameters.len() / 2);
+        assert_eq!(provider.generate(range(12), &sequence.parameters, &[]), Ok(terms));
+    }
+
+    #[test]
+    fn test_find_linear_recurrence_limits() {
+        let query = |terms: Vec<Term>, modulus| RecurrenceQuery { terms, modulus };
+        let numbers = |values: &[f64]| values.iter().map(|value| Term::Number(*value)).collect::<Vec<_>>();
+        let error = |query: &RecurrenceQuery, limits: &AnalysisLimits| serde_json::to_value(find_linear_recurrence(query, limits).unwrap_err()).unwrap();
+
+        // periodično zaporedje s periodo 5 ima rekurzijo stopnje 5, ki je ne ponujamo
+        let periodic = query(numbers(&[1., 2., 3., 4., 5., 1., 2., 3., 4., 5.]), None);
+        let too_deep = error(&periodic, &LIMITS);
+        assert_eq!((too_deep["error"].as_str(), too_deep["extra"]["degree"].as_u64()), (Some("MissingProvider"), Some(5)));
+        let sequence = find_linear_recurrence(&periodic, &AnalysisLimits { max_degree: 5, ..LIMITS }).unwrap();
+        assert_eq!(sequence.parameters, vec![0., 0., 0., 0., 1., 1., 2., 3., 4., 5.]);
+        let periodic = query(numbers(&[1., 2., 3., 4., 5., 1., 2., 3., 4., 5.]), Some(7));
+        assert_eq!(error(&periodic, &LIMITS)["error"], "MissingProvider");
+
+        // velikih členov ne iščemo, saj bi Berlekamp-Massey nad njimi tekel predolgo
+        let random: Vec<Term> = (0..200u64).map(|n| Term::Exact((n * 2_654_435_761 % 4_294_967_291).to_string())).collect();
+        assert_eq!(error(&query(random.clone(), None), &LIMITS)["extra"]["budget"], "cost");
+        assert_eq!(error(&query(random, None), &AnalysisLimits { max_bits: 1024, ..LIMITS })["extra"]["budget"], "bits");
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{
+    error::{Error, Result},
+    parse::sequence_provide::{self, SequenceInfo},
+    sequnce_providers::FunctionSequence
+};
+
+/// Največja globina gnezdenja oklepajev in operacij v formuli
+const MAX_DEPTH: usize = 64;
+
+/// Največje število operandov (števil, spremenljivk, oklepajev in klicev funkcij) v formuli. Verige operacij,
+/// npr. `n + n + ...`, niso gnezdene, a drevo formule vseeno poglobijo, zato jih omejimo s številom operandov.
+const MAX_OPERANDS: usize = 1024;
+
+/// Največje število parametrov zaporedja, podanega s formulo (parametri so `p0, ..., p255`)
+const MAX_PARAMETERS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator { Add, Sub, Mul, Div, Rem, Pow }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function { Pow, Sin, Cos, Tan, Exp, Ln, Log, Sqrt, Abs, Floor, Ceil, Round, Min, Max }
+
+impl Function {
+    const ALL: [(&'static str, Function); 14] = [
+        ("pow", Function::Pow), ("sin", Function::Sin), ("cos", Function::Cos), ("tan", Function::Tan),
+        ("exp", Function::Exp), ("ln", Function::Ln), ("log", Function::Log), ("sqrt", Function::Sqrt),
+        ("abs", Function::Abs), ("floor", Function::Floor), ("ceil", Function::Ceil), ("round", Function::Round),
+        ("min", Function::Min), ("max", Function::Max)
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Function::ALL.iter().find(|(function, _)| *function == name).map(|(_, function)| *function)
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Pow | Function::Log | Function::Min | Function::Max => 2,
+            _ => 1
+        }
+    }
+
+    fn apply(&self, arguments: &[f64]) -> f64 {
+        let x = arguments[0];
+        match self {
+            Function::Pow   => x.powf(arguments[1]),
+            Function::Sin   => x.sin(),
+            Function::Cos   => x.cos(),
+            Function::Tan   => x.tan(),
+            Function::Exp   => x.exp(),
+            Function::Ln    => x.ln(),
+            Function::Log   => x.log(arguments[1]),
+            Function::Sqrt  => x.sqrt(),
+            Function::Abs   => x.abs(),
+            Function::Floor => x.floor(),
+            Function::Ceil  => x.ceil(),
+            Function::Round => x.round(),
+            Function::Min   => x.min(arguments[1]),
+            Function::Max   => x.max(arguments[1])
+        }
+    }
+}
+
+/// Drevo formule
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    /// Indeks člena `n`
+    Index,
+    /// Parameter `p<i>`
+    Parameter(usize),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>)
+}
+
+impl Expr {
+    fn evaluate(&self, parameters: &[f64], n: f64) -> f64 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Index => n,
+            Expr::Parameter(i) => parameters[*i],
+            Expr::Negate(x) => -x.evaluate(parameters, n),
+            Expr::Binary(operator, a, b) => {
+                let (a, b) = (a.evaluate(parameters, n), b.evaluate(parameters, n));
+                match operator {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                    Operator::Rem => a.rem_euclid(b),
+                    Operator::Pow => a.powf(b)
+                }
+            },
+            Expr::Call(function, arguments) => {
+                let arguments: Vec<f64> = arguments.iter().map(|argument| argument.evaluate(parameters, n)).collect();
+                function.apply(&arguments)
+            }
+        }
+    }
+
+    /// Število parametrov, ki jih formula uporablja (največji indeks + 1)
+    fn parameters(&self) -> usize {
+        match self {
+            Expr::Number(_) | Expr::Index => 0,
+            Expr::Parameter(i) => i + 1,
+            Expr::Negate(x) => x.parameters(),
+            Expr::Binary(_, a, b) => a.parameters().max(b.parameters()),
+            Expr::Call(_, arguments) => arguments.iter().map(Expr::parameters).max().unwrap_or(0)
+        }
+    }
+}
+
+struct Parser<'a> {
+    formula:    &'a str,
+    chars:      Vec<char>,
+    position:   usize,
+    depth:      usize,
+    operands:   usize
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, reason: &str) -> Error {
+        Error::invalid_expression(self.formula, self.position + 1, reason)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.position).is_some_and(|c| c.is_whitespace()) { self.position += 1; }
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) { self.position += 1; true } else { false }
+    }
+
+    // prebere znake, dokler velja `predicate`
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| predicate(*c)) { self.position += 1; }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    // vsak nivo gnezdenja preveri globino, da nas globoko gnezdena formula ne more spraviti v stack overflow
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH { return Err(self.error("Formula je pregloboko gnezdena")); }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr> {
+        let mut left = self.term()?;
+        loop {
+            let operator = if self.eat('+') { Operator::Add } else if self.eat('-') { Operator::Sub } else { return Ok(left) };
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.term()?));
+        }
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let operator =
+                if self.eat('*') { Operator::Mul }
+                else if self.eat('/') { Operator::Div }
+                else if self.eat('%') { Operator::Rem }
+                else { return Ok(left) };
+            left = Expr::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    // unary := '-' unary | power, tako da je -2^2 = -(2^2)
+    fn unary(&mut self) -> Result<Expr> {
+        self.nested(|parser| {
+            if parser.eat('-') { Ok(Expr::Negate(Box::new(parser.unary()?))) }
+            else { parser.power() }
+        })
+    }
+
+    // power := atom ('^' unary)?, potenciranje je desno asociativno
+    fn power(&mut self) -> Result<Expr> {
+        let base = self.atom()?;
+        if self.eat('^') { Ok(Expr::Binary(Operator::Pow, Box::new(base), Box::new(self.unary()?))) }
+        else { Ok(base) }
+    }
+
+    fn atom(&mut self) -> Result<Expr> {
+        let next = self.peek();
+        if self.operands >= MAX_OPERANDS {
+            return Err(self.error(&format!("Formula ima lahko največ {MAX_OPERANDS} operandov")));
+        }
+        self.operands += 1;
+
+        match next {
+            Some('(') => {
+                self.position += 1;
+                let expr = self.nested(Self::expr)?;
+                if self.eat(')') { Ok(expr) } else { Err(self.error("Pričakovan ')'")) }
+            },
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position;
+                let mut number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                // eksponent, npr. 1e-3
+                if matches!(self.chars.get(self.position), Some('e' | 'E'))
+                    && self.chars.get(self.position + 1).is_some_and(|c| c.is_ascii_digit() || *c == '-' || *c == '+') {
+                    number.push(self.chars[self.position]);
+                    number.push(self.chars[self.position + 1]);
+                    self.position += 2;
+                    number.push_str(&self.take_while(|c| c.is_ascii_digit()));
+                }
+                number.parse().map(Expr::Number).map_err(|_| {
+                    self.position = start;
+                    self.error("Neveljavno število")
+                })
+            },
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let start = self.position;
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                match name.as_str() {
+                    "n" => Ok(Expr::Index),
+                    "pi" => Ok(Expr::Number(std::f64::consts::PI)),
+                    "e" => Ok(Expr::Number(std::f64::consts::E)),
+                    name if name.len() > 1 && name.starts_with('p') && name[1..].chars().all(|c| c.is_ascii_digit()) => match name[1..].parse() {
+                        Ok(index) if index < MAX_PARAMETERS => Ok(Expr::Parameter(index)),
+                        _ => {
+                            self.position = start;
+                            Err(self.error(&format!("Indeks parametra je lahko največ {}", MAX_PARAMETERS - 1)))
+                        }
+                    },
+                    name => match Function::from_name(name) {
+                        Some(function) => self.call(function),
+                        None => {
+                            self.position = start;
+                            Err(self.error(&format!("Neznana spremenljivka ali funkcija `{name}`")))
+                        }
+                    }
+                }
+            },
+            _ => Err(self.error("Pričakovano število, spremenljivka ali '('"))
+        }
+    }
+
+    fn call(&mut self, function: Function) -> Result<Expr> {
+        if !self.eat('(') { return Err(self.error("Pričakovan '('")); }
+
+        let mut arguments = vec![];
+        loop {
+            arguments.push(self.nested(Self::expr)?);
+            if self.eat(')') { break; }
+            if !self.eat(',') { return Err(self.error("Pričakovan ',' ali ')'")); }
+        }
+
+        if arguments.len() != function.arity() {
+            return Err(self.error(&format!("Funkcija sprejme {} argumentov", function.arity())));
+        }
+        Ok(Expr::Call(function, arguments))
+    }
+}
+
+/// Zaporedje, podano s formulo v spremenljivki `n` in parametrih `p0, p1, ...`, npr. `p0 * sin(n / p1) + floor(n^2 / 3)`.
+/// Formula lahko uporablja `+ - * / % ^`, konstanti `pi` in `e` ter funkcije `pow`, `sin`, `cos`, `tan`, `exp`, `ln`,
+/// `log(x, osnova)`, `sqrt`, `abs`, `floor`, `ceil`, `round`, `min` in `max`.
+pub struct Sequence {
+    info:       SequenceInfo,
+    formula:    Expr
+}
+
+impl Sequence {
+    /// Prebere formulo. Če število parametrov ni podano, je enako številu parametrov, ki jih formula uporablja.
+    pub fn new(name: &str, formula: &str, parameters: Option<usize>, description: Option<&str>) -> Result<Self> {
+        let mut parser = Parser { formula, chars: formula.chars().collect(), position: 0, depth: 0, operands: 0 };
+        let expr = parser.expr()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Pričakovan konec formule"));
+        }
+
+        let used = expr.parameters();
+        let parameters = parameters.unwrap_or(used);
+        if parameters > MAX_PARAMETERS {
+            return Err(Error::invalid_expression(formula, 1, &format!("Zaporedje ima lahko največ {MAX_PARAMETERS} parametrov")));
+        }
+        if parameters < used {
+            return Err(Error::invalid_expression(formula, 1, &format!("Formula uporablja parameter p{}, zaporedje pa ima le {parameters} parametrov", used - 1)));
+        }
+
+        let valid_name = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(Error::invalid_expression(name, 1, "Ime zaporedja lahko vsebuje le črke, števke in `_`, in se ne sme začeti s števko"));
+        }
+
+        let names: Vec<String> = (0..parameters).map(|i| format!("p{i}")).collect();
+        let description = format!(
+            "{}f(n) = {formula}. Parametri: [{}]",
+            description.map_or("Zaporedje, podano s formulo ".to_owned(), |description| format!("{description}, ")),
+            names.join(", ")
+        );
+
+        Ok(Sequence {
+            info: SequenceInfo { name: name.to_owned(), description, parameters, sequences: 0 },
+            formula: expr
+        })
+    }
+}
+
+impl FunctionSequence for Sequence {
+    fn get_info(&self) -> sequence_provide::SequenceInfo {
+        self.info.clone()
+    }
+
+    fn evaluate(&self, parameters: &[f64], n: u64) -> Result<f64> {
+        if parameters.len() != self.info.parameters {
+            Err(Error::sequence_arithmetic_error(self.get_info(), "Število parametrov je nepravilno."))
+        } else {
+            Ok(self.formula.evaluate(parameters, n as f64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequnce_providers::FunctionSequence;
+    use super::Sequence;
+
+    fn evaluate(formula: &str, parameters: &[f64], n: u64) -> f64 {
+        Sequence::new("f", formula, None, None).unwrap().evaluate(parameters, n).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("p0 + p1 * n", &[1., 2.], 3), 7.);
+        assert_eq!(evaluate("-2^2 + 2^3^2", &[], 0), 508.);
+        assert_eq!(evaluate("(n - 7) % 5 + floor(n / 2)", &[], 3), 2.);
+        assert_eq!(evaluate("max(pow(p0, 2), 3) + abs(-1.5e1)", &[-2.], 0), 19.);
+        assert_eq!(evaluate("round(sin(pi / 2) * 10) + log(8, 2) + ln(e)", &[], 0), 14.);
+
+        let sequence = Sequence::new("kvadrat", "p1 * n^2", Some(3), Some("Kvadrat")).unwrap();
+        assert_eq!((sequence.get_info().parameters, sequence.get_info().description.as_str()), (3, "Kvadrat, f(n) = p1 * n^2. Parametri: [p0, p1, p2]"));
+        assert!(sequence.evaluate(&[1., 2.], 1).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let column = |formula: &str| serde_json::to_value(Sequence::new("f", formula, None, None).err().unwrap()).unwrap()["extra"]["column"].as_u64();
+
+        assert_eq!(column("p0 + * n"), Some(6));
+        assert_eq!(column("sinh(n)"), Some(1));
+        assert_eq!(column("pow(n)"), Some(7));
+        assert_eq!(column("(n + 1"), Some(7));
+        assert_eq!(column("n n"), Some(3));
+        assert_eq!(column(&"(".repeat(100)), Some(33));
+        assert_eq!(column("n + p18446744073709551615"), Some(5));
+        assert_eq!(column("p256 * n"), Some(1));
+        assert_eq!(column("p99999999999999999999999"), Some(1));
+        assert!(Sequence::new("f", "p255 * n", None, None).is_ok());
+        assert!(Sequence::new("f", "n", Some(usize::MAX), None).is_err());
+
+        // dolga veriga operacij ni gnezdena, a drevo vseeno poglobi
+        let chain = |operands: usize, operator: &str| vec!["n"; operands].join(operator);
+        assert_eq!(column(&chain(1_000_000, "+")), Some(2049));
+        assert_eq!(column(&chain(2000, " * ")), Some(4097));
+        assert_eq!(column(&format!("-{}", chain(1025, "-"))), Some(2050));
+        assert_eq!(evaluate(&chain(1024, "+"), &[], 2), 2048.);
+
+        assert!(Sequence::new("f", "p2 * n", Some(2), None).is_err());
+        assert!(Sequence::new("1f", "n", None, None).is_err());
+    }
+}
@@ -1,15 +1,27 @@
-use std::vec;
+use std::{sync::Arc, vec};
 
 use crate::{
-    error::Result, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::SequenceProvider
+    error::{Error, Result}, 
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::{self, Exact}, SequenceProvider, Terms}
 };
 
 /// Zaporedje, ki ga lahko predstavimo kot funkcija nekih parametrov in indeksa
 pub trait FunctionSequence : Sync + Send {
     fn evaluate(&self, parameters: &[f64], n: u64) -> Result<f64>;
     fn get_info(&self) -> sequence_provide::SequenceInfo;
+
+    /// Domene, v katerih zna zaporedje računati. Za točne domene mora implementirati `evaluate_exact`.
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float]
+    }
+    fn evaluate_exact(&self, _parameters: &[Exact], _n: u64) -> Result<Exact> {
+        Err(Error::sequence_arithmetic_error(self.get_info(), "Zaporedje ne podpira točnega računanja."))
+    }
+    /// Oceni število bitov točnega člena z indeksom `n` (privzeto vsota velikosti parametrov in indeksa)
+    fn estimate_bits(&self, parameters: &[f64], n: u64) -> u64 {
+        (parameters.iter().map(|parameter| exact::bits(*parameter)).sum::<f64>() + (n as f64 + 1.).log2()).ceil() as u64
+    }
 }
 
 pub struct FunctionSequenceProvider {
@@ -30,18 +42,42 @@ impl SequenceProvider for FunctionSequenceProvider {
     fn generate(&self,range:sequence_provide::Range,parameters: &[f64], _: &[Vec<f64>]) -> Result<Vec<f64> > {
         let mut result = vec![];        
 
-        let mut i = range.from; 
-        while i < range.to {
-            result.push(self.base.evaluate(parameters, i)?); i += range.step;
+        for i in (range.from..range.to).step_by(range.step.try_into()?) {
+            result.push(self.base.evaluate(parameters, i)?);
         }
 
         Ok(result)
     }
+
+    fn generate_iter(self: Arc<Self>, range: sequence_provide::Range, parameters: Vec<f64>, _: Vec<Vec<f64>>) -> Result<Terms> {
+        let indices = (range.from..range.to).step_by(range.step.try_into()?);
+        Ok(Box::new(indices.map(move |i| self.base.evaluate(&parameters, i))))
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        self.base.domains()
+    }
+
+    // členi so največji na koncu range-a
+    fn estimate_bits(&self, range: sequence_provide::Range, parameters: &[f64]) -> u64 {
+        self.base.estimate_bits(parameters, range.to.saturating_sub(1))
+    }
+
+    fn generate_exact(&self, range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], _: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        (range.from..range.to).step_by(range.step.try_into()?)
+            .map(|i| self.base.evaluate_exact(parameters, i))
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sequnce_providers::{implementations::*, SequenceProvider};
+    use std::sync::Arc;
+
+    use crate::{
+        error::Result, parse::sequence_provide::{Domain, Range},
+        sequnce_providers::{exact, implementations::*, SequenceProvider}
+    };
     use super::FunctionSequenceProvider;
 
     #[test]
@@ -56,5 +92,16 @@ mod tests {
             fs.generate(crate::parse::sequence_provide::Range { from: 0, to: 10, step: 1 }, &[0., 2.], &[]),
             Ok(vec![0., 2., 4., 6., 8., 10., 12., 14., 16., 18.])
         );
+
+        let terms = Arc::new(fs).generate_iter(crate::parse::sequence_provide::Range { from: 4, to: 10, step: 3 }, vec![0., 2.], vec![]);
+        assert_eq!(terms.unwrap().collect::<Result<Vec<f64>>>(), Ok(vec![8., 14.]));
+    }
+
+    #[test]
+    fn test_exact() {
+        let fs = FunctionSequenceProvider::new(Box::new(geometric::Sequence {}));
+        let parameters = [exact::from_integer(3), exact::from_integer(10)];
+        let terms = fs.generate_exact(Range { from: 20, to: 22, step: 1 }, Domain::Integer, &parameters, &[]).unwrap();
+        assert_eq!(exact::to_strings(&terms), vec!["300000000000000000000", "3000000000000000000000"]);
     }
 }
\ No newline at end of file
@@ -1,2 +1,3 @@
+pub mod formula;
 pub mod function;
 pub mod operation;
\ No newline at end of file
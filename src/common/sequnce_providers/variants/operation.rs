@@ -1,7 +1,9 @@
+use std::sync::Arc;
+
 use crate::{
     error::{Error, Result}, 
-    parse::sequence_provide::{self}, 
-    sequnce_providers::SequenceProvider
+    parse::sequence_provide::{self, Domain}, 
+    sequnce_providers::{exact::Exact, SequenceProvider, Terms}
 };
 
 /// Zaporedje, ki ga lahko predstavimo kot neka operacija nad istoležečimi členi drugih zaporedih
@@ -9,6 +11,14 @@ pub trait OperationSequence : Sync + Send {
     /// Ni potrebno skrbeti za napačno število parametrov ali zaporedij, garaniramo, da se ujema z get_info.
     fn apply(&self, parameters: &[f64], sequences: &[f64]) -> Result<f64>;
     fn get_info(&self) -> sequence_provide::SequenceInfo;
+
+    /// Domene, v katerih zna zaporedje računati. Za točne domene mora implementirati `apply_exact`.
+    fn domains(&self) -> Vec<Domain> {
+        vec![Domain::Float]
+    }
+    fn apply_exact(&self, _parameters: &[Exact], _sequences: &[Exact]) -> Result<Exact> {
+        Err(Error::sequence_arithmetic_error(self.get_info(), "Zaporedje ne podpira točnega računanja."))
+    }
 }
 
 pub struct OperationSequenceProvider {
@@ -16,7 +26,8 @@ pub struct OperationSequenceProvider {
 }
 
 impl OperationSequenceProvider {
-    fn combine(&self, length: usize, parameters: &[f64], sequences: &[Vec<f64>]) -> Result<Vec<f64>> {
+    // preveri, da se število parametrov, zaporedij in dolžine zaporedij ujemajo
+    fn check<T>(&self, length: usize, parameters: &[T], sequences: &[Vec<T>]) -> Result<()> {
 
         let info = self.get_info();
         if info.sequences != sequences.len() || info.parameters != parameters.len() {
@@ -25,17 +36,19 @@ impl OperationSequenceProvider {
 
         if !sequences.iter().all(|s| s.len() == length) { 
             Err(Error::sequence_arithmetic_error(info, "Pridobljene dolžine zaporedij se ne ujemajo"))
-        } else {
-            let mut result = vec![];
-
-            for i in 0..length {
-                let collect: Vec<f64> = sequences.iter().map(|s| s[i]).collect();
-                result.push(self.base.apply(parameters, &collect)?);
-            }
-            
-            Ok(result)
-        }
+        } else { Ok(()) }
+    }
+
+    fn combine<T: Clone>(&self, length: usize, parameters: &[T], sequences: &[Vec<T>], apply: impl Fn(&[T], &[T]) -> Result<T>) -> Result<Vec<T>> {
+        self.check(length, parameters, sequences)?;
 
+        let mut result = vec![];
+        for i in 0..length {
+            let collect: Vec<T> = sequences.iter().map(|s| s[i].clone()).collect();
+            result.push(apply(parameters, &collect)?);
+        }
+        
+        Ok(result)
     }
 
     pub fn new(base: Box<dyn OperationSequence>) -> Self {
@@ -48,13 +61,34 @@ impl OperationSequenceProvider {
 impl SequenceProvider for OperationSequenceProvider {
     fn get_info(&self) -> sequence_provide::SequenceInfo { self.base.get_info().clone() }
     fn generate(&self, _range:sequence_provide::Range, parameters: &[f64],sequences: &[Vec<f64>]) -> Result<Vec<f64> > {
-        Ok(self.combine(sequences[0].len(), parameters, sequences)?)
+        self.combine(sequences[0].len(), parameters, sequences, |parameters, terms| self.base.apply(parameters, terms))
+    }
+
+    fn generate_iter(self: Arc<Self>, _range: sequence_provide::Range, parameters: Vec<f64>, sequences: Vec<Vec<f64>>) -> Result<Terms> {
+        let length = sequences.first().map_or(0, Vec::len);
+        self.check(length, &parameters, &sequences)?;
+
+        Ok(Box::new((0..length).map(move |i| {
+            let collect: Vec<f64> = sequences.iter().map(|s| s[i]).collect();
+            self.base.apply(&parameters, &collect)
+        })))
+    }
+
+    fn domains(&self) -> Vec<Domain> {
+        self.base.domains()
+    }
+
+    fn generate_exact(&self, _range: sequence_provide::Range, _domain: Domain, parameters: &[Exact], sequences: &[Vec<Exact>]) -> Result<Vec<Exact>> {
+        let length = sequences.first().map_or(0, Vec::len);
+        self.combine(length, parameters, sequences, |parameters, terms| self.base.apply_exact(parameters, terms))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sequnce_providers::{implementations::*, SequenceProvider};
+    use std::sync::Arc;
+
+    use crate::{error::Result, parse::sequence_provide::Range, sequnce_providers::{implementations::*, SequenceProvider}};
     use super::OperationSequenceProvider;
 
     #[test]
@@ -65,5 +99,8 @@ mod tests {
             fs.generate(crate::parse::sequence_provide::Range { from: 0, to: 4, step: 1 }, &[], &[vec![1.,4.,8.,13.], vec![3.,2.,13.,1.]]),
             Ok(vec![3.,4.,13.,13.])
         );
+
+        let terms = Arc::new(fs).generate_iter(Range { from: 0, to: 4, step: 1 }, vec![], vec![vec![1.,4.,8.,13.], vec![3.,2.,13.,1.]]);
+        assert_eq!(terms.unwrap().collect::<Result<Vec<f64>>>(), Ok(vec![3.,4.,13.,13.]));
     }
 }
\ No newline at end of file